@@ -1,6 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::smallvec;
 
 use seedgen::*;
@@ -12,6 +12,7 @@ use player::*;
 use pool::*;
 use item::*;
 use util::*;
+use inventory::*;
 use settings::*;
 
 fn parsing(c: &mut Criterion) {
@@ -29,7 +30,7 @@ fn parsing(c: &mut Criterion) {
     let states = logic::parse_states(&input).unwrap();
 
     let mut settings = Settings::default();
-    settings.difficulty = Difficulty::Unsafe;
+    settings.difficulty = String::from("unsafe");
 
     c.bench_function("emit", |b| b.iter(|| emitter::emit(&areas, &metadata, &locations, &states, &settings, false)));
 }
@@ -74,6 +75,35 @@ fn requirements(c: &mut Criterion) {
     c.bench_function("long combat progression", |b| b.iter(|| req.items_needed(&player, &states)));
 }
 
+fn inventory(c: &mut Criterion) {
+    let mut inventory = Pool::preset().inventory;
+    inventory.grant(Item::SpiritLight(1), 10000);
+    inventory.grant(Item::Teleporter(Teleporter::Marsh), 1);
+    inventory.grant(Item::Message(String::from("exotic")), 1);
+
+    let lookups = [Item::Skill(Skill::Bash), Item::Shard(Shard::Overcharge), Item::Resource(Resource::Energy), Item::Teleporter(Teleporter::Glades), Item::Water, Item::SpiritLight(1)];
+    c.bench_function("inventory lookups", |b| b.iter(|| lookups.iter().filter(|item| inventory.has(item, 1)).count()));
+    let needed = Inventory::from(vec![(Item::Skill(Skill::Grapple), 1), (Item::Shard(Shard::Wingclip), 1), (Item::Resource(Resource::Health), 10), (Item::SpiritLight(1), 1200)]);
+    c.bench_function("inventory contains", |b| b.iter(|| inventory.contains(&needed)));
+    c.bench_function("inventory merge", |b| b.iter(|| inventory.merge(&needed)));
+
+    // the same operations on a plain map, which is how the inventory used to store every item
+    let baseline = inventory.iter().collect::<FxHashMap<_, _>>();
+    let baseline_needed = needed.iter().collect::<FxHashMap<_, _>>();
+    c.bench_function("hashmap inventory lookups", |b| b.iter(|| lookups.iter().filter(|item| baseline.get(item).copied().unwrap_or(0) >= 1).count()));
+    c.bench_function("hashmap inventory contains", |b| b.iter(|| baseline_needed.iter().all(|(item, amount)| baseline.get(item).unwrap_or(&0) >= amount)));
+
+    let mut player = Player::default();
+    player.difficulty = Difficulty::Unsafe;
+    player.inventory = inventory.clone();
+    let states = FxHashSet::default();
+    let req = Requirement::Or(vec![
+        Requirement::And(vec![Requirement::Skill(Skill::Grapple), Requirement::Shard(Shard::UltraGrapple), Requirement::Teleporter(Teleporter::Burrows)]),
+        Requirement::And(vec![Requirement::Skill(Skill::Launch), Requirement::Water, Requirement::SpiritLight(400)]),
+    ]);
+    c.bench_function("item requirements", |b| b.iter(|| req.is_met(&player, &states, player.max_orbs())));
+}
+
 fn reach_checking(c: &mut Criterion) {
     let graph = parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &Settings::default(), false).unwrap();
 
@@ -108,20 +138,21 @@ fn generation(c: &mut Criterion) {
 
     c.bench_function("singleplayer", |b| b.iter(|| {
         let graph = parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
-        seedgen::generate_seed(&graph, settings.clone(), &vec![], None).unwrap();
+        seedgen::generate_seed(&graph, settings.clone(), &vec![], None, false).unwrap();
     }));
 
     settings.worlds = 2;
 
     c.bench_function("two worlds", |b| b.iter(|| {
         let graph = parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
-        seedgen::generate_seed(&graph, settings.clone(), &vec![], None).unwrap();
+        seedgen::generate_seed(&graph, settings.clone(), &vec![], None, false).unwrap();
     }));
 }
 
-criterion_group!(all, parsing, requirements, reach_checking, generation);
+criterion_group!(all, parsing, requirements, inventory, reach_checking, generation);
 criterion_group!(only_parsing, parsing);
 criterion_group!(only_requirements, requirements);
+criterion_group!(only_inventory, inventory);
 criterion_group!(only_reach_checking, reach_checking);
 criterion_group!(only_generation, generation);
-criterion_main!(only_reach_checking);  // put any of the group names in here
+criterion_main!(only_inventory, only_reach_checking);  // put any of the group names in here
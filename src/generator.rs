@@ -1,6 +1,7 @@
 use std::{
     fmt,
     collections::HashMap,
+    convert::TryFrom,
 };
 
 use rand::{
@@ -13,13 +14,14 @@ use crate::{
     ItemDetails,
     inventory::Inventory,
     item::{Item, Resource, Skill, Teleporter, Command, ShopCommand},
-    settings::Settings, util::{
+    languages::headers::parser,
+    settings::{Settings, Constraint, GeneratorConfig}, util::{
         self,
-        GoalMode, UberState, UberType, Difficulty,
-        constants::{RELIC_ZONES, KEYSTONE_DOORS, RESERVE_SLOTS, PLACEHOLDER_SLOTS, SHOP_PRICES, DEFAULT_SPAWN, RANDOM_PROGRESSION},
+        GoalMode, CustomGoal, UberState, UberIdentifier, UberType, Difficulty,
+        constants::{RELIC_ZONES, KEYSTONE_DOORS, KEYSTONE_COST_GROUP, SHOP_PRICES, DEFAULT_SPAWN},
     }, world::{
         World,
-        graph::{self, Node},
+        graph::{self, Graph, Node, DoorRedirect},
         player::Player,
     }
 };
@@ -36,6 +38,13 @@ impl fmt::Display for Placement<'_> {
     }
 }
 
+/// The total Spirit Light one world receives from its placements, including what other worlds send to it
+pub fn spirit_light_total(world_placements: &[Placement]) -> u32 {
+    world_placements.iter()
+        .filter_map(|placement| if let Item::SpiritLight(amount) = placement.item { Some(u32::from(amount)) } else { None })
+        .sum()
+}
+
 #[derive(Debug)]
 pub enum PartialItem {
     Placeholder,
@@ -52,9 +61,20 @@ struct WorldContext<'a> {
     spawn_slots: Vec<&'a Node>,
     reachable_locations: Vec<&'a Node>,
     unreachable_locations: Vec<&'a Node>,
-    spirit_light_rng: SpiritLightAmounts,  // TODO this can get kinda weird maybe have a shared spirit light rng instead
     random_spirit_light: Bernoulli,
     shop_slots: usize,
+    shop_prices: Vec<ShopPrice>,
+    progression_spent: u32,  // how much Spirit Light the progression placed in shops so far costs
+    reach_state: Option<graph::ReachState<'a>>,  // the last progression check, to continue from in the next one
+    reached_count: usize,  // how many locations the last progression check reached
+    spheres: usize,  // how many progression checks reached new locations, which is how deep into its spheres the world is
+}
+
+struct ShopPrice {
+    placement_index: usize,
+    uber_identifier: UberIdentifier,
+    price: u16,
+    adjustable: bool,
 }
 
 struct GeneratorContext<'a, 'b, R, I>
@@ -64,10 +84,15 @@ where
 {
     world_count: usize,
     total_reachable_count: usize,
-    custom_items: &'b HashMap<String, ItemDetails>,
+    custom_items: &'b [HashMap<String, ItemDetails>],  // custom items are defined by the headers of the world receiving the item
+    constraints: &'b [(Item, Constraint)],
     multiworld_state_index: I,
     price_range: Uniform<f32>,
+    max_price_modifier: f32,
     random_progression: Bernoulli,
+    progression_balancing: f32,
+    avoid_softlocks: bool,
+    spirit_light: SpiritLightDistributor,
     rng: &'a mut R,
 }
 
@@ -94,6 +119,147 @@ fn format_identifiers(mut identifiers: Vec<&str>) -> String {
     identifiers
 }
 
+fn in_zone(node: &Node, zone: &str) -> bool {
+    node.zone().map_or(false, |node_zone| node_zone.to_string().eq_ignore_ascii_case(zone))
+}
+fn is_excluded(node: &Node, excluded: &str) -> bool {
+    node.identifier() == excluded || in_zone(node, excluded)
+}
+
+pub fn warn_unknown_exclusions(graph: &Graph, settings: &Settings) {
+    for excluded in &settings.exclude {
+        if !graph.nodes.iter().any(|node| node.can_place() && is_excluded(node, excluded)) {
+            log::warn!("Unknown location or zone {} in the excluded locations", excluded);
+        }
+    }
+}
+
+/// Find the nodes meeting each condition of a custom goal, a condition is met once any of its nodes is reached
+pub fn custom_goal_conditions(graph: &Graph, goal: &CustomGoal) -> Result<Vec<Vec<usize>>, String> {
+    goal.conditions.iter().map(|condition| {
+        let uber_state = condition.parse::<UberState>().ok();
+
+        let nodes = graph.nodes.iter()
+            .filter(|&node| node.identifier() == condition || uber_state.as_ref().map_or(false, |uber_state|
+                node.uber_state().map_or(false, |node_uber_state|
+                    node_uber_state.identifier == uber_state.identifier &&
+                    (uber_state.value.is_empty() || node_uber_state.value == uber_state.value))))
+            .map(Node::index)
+            .collect::<Vec<_>>();
+
+        if nodes.is_empty() {
+            Err(format!("Unknown condition {} in goal {}", condition, goal.name))
+        } else { Ok(nodes) }
+    }).collect()
+}
+
+/// Check that every world can fulfill the conditions of the custom goals
+pub fn verify_custom_goals(worlds: &[World], settings: &Settings) -> Result<(), String> {
+    for goal in settings.goalmodes.iter().filter_map(|goalmode| if let GoalMode::Custom(goal) = goalmode { Some(goal) } else { None }) {
+        if goal.name.contains(',') {
+            return Err(format!("Goal names can't contain commas: {}", goal.name));
+        }
+        let required = goal.required.unwrap_or(goal.conditions.len());
+        if required > goal.conditions.len() {
+            return Err(format!("Goal {} requires {} conditions, but only has {}", goal.name, required, goal.conditions.len()));
+        }
+
+        for (world_index, world) in worlds.iter().enumerate() {
+            let player_name = settings.players.get(world_index).cloned().unwrap_or_else(|| format!("Player {}", world_index + 1));
+
+            let conditions = custom_goal_conditions(world.graph, goal)?;
+            let reachable = total_reach_check(world, &player_name)?;
+            let reachable_count = conditions.iter().filter(|nodes| nodes.iter().any(|&index| reachable.iter().any(|&node| node.index() == index))).count();
+
+            if reachable_count < required {
+                return Err(format!("({}): Only {} of the {} conditions required for the goal {} are reachable on these settings", player_name, reachable_count, required, goal.name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Shuffle teleporter destinations and doors of the world's graph as the settings ask until everything that was reachable before stays reachable
+///
+/// Teleporters which are preplaced, e.g. as starting items, keep their destination since headers grant them as they are
+pub fn randomize_entrances<R: Rng>(world: &World, player_name: &str, settings: &Settings, rng: &mut R) -> Result<Graph, String> {
+    let reachable_count = |world: &World| -> Result<usize, String> {
+        Ok(total_reach_check(world, player_name)?.iter().filter(|&&node| node.can_place()).count())
+    };
+    let expected = reachable_count(world)?;
+    let fixed = world.preplacements.values().flatten()
+        .filter_map(|item| if let Item::Teleporter(teleporter) = item { Some(*teleporter) } else { None })
+        .collect::<Vec<_>>();
+
+    for attempt in 1..=settings.generator.retries {
+        let mut graph = if settings.randomize_teleporters { world.graph.shuffle_teleporters(&fixed, rng)? } else { world.graph.clone() };
+        if settings.randomize_doors { graph = graph.shuffle_doors(rng); }
+
+        let shuffled_world = World { graph: &graph, ..world.clone() };
+        let reached = reachable_count(&shuffled_world)?;
+        if reached >= expected {
+            log::trace!("({}): Shuffled {} teleporters and {} doors", player_name, graph.redirects.len(), graph.door_redirects.len());
+            return Ok(graph);
+        }
+
+        log::trace!("({}): Attempt {} at shuffling entrances only reaches {} of {} locations", player_name, attempt, reached, expected);
+    }
+
+    Err(format!("({}): Failed to shuffle entrances without making locations unreachable", player_name))
+}
+
+pub fn parse_constraints(graph: &Graph, settings: &Settings) -> Result<Vec<(Item, Constraint)>, String> {
+    settings.constraints.iter().map(|placement_constraint| {
+        let item = parser::parse_item(&placement_constraint.item).map_err(|err| format!("Invalid placement constraint: {}", err))?;
+
+        if let Constraint::InZone(zone) = &placement_constraint.constraint {
+            if !graph.nodes.iter().any(|node| node.can_place() && in_zone(node, zone)) {
+                return Err(format!("Unknown zone {} in placement constraint for {}", zone, item));
+            }
+        }
+        if let Constraint::InWorld(world) = placement_constraint.constraint {
+            if world == 0 || world > settings.worlds {
+                return Err(format!("Placement constraint for {} refers to world {}, but there are only {} worlds", item, world, settings.worlds));
+            }
+        }
+
+        Ok((item, placement_constraint.constraint.clone()))
+    }).collect()
+}
+
+fn meets_constraints(item: &Item, origin_world_index: usize, node: &Node, constraints: &[(Item, Constraint)]) -> bool {
+    constraints.iter()
+        .filter(|(constrained, _)| constrained == item)
+        .all(|(_, constraint)| match constraint {
+            Constraint::InZone(zone) => in_zone(node, zone),
+            Constraint::NotInShop => !node.uber_state().map_or(false, UberState::is_purchasable),
+            Constraint::InWorld(world) => origin_world_index + 1 == *world,
+        })
+}
+
+fn take_allowed<'a>(nodes: &mut Vec<&'a Node>, item: &Item, origin_world_index: usize, constraints: &[(Item, Constraint)]) -> Option<&'a Node> {
+    let index = nodes.iter().rposition(|&node| meets_constraints(item, origin_world_index, node, constraints))?;
+    Some(nodes.remove(index))
+}
+
+fn max_shop_price(item: &Item, custom_items: &HashMap<String, ItemDetails>, max_price_modifier: f32) -> f32 {
+    let price = f32::from(custom_items.get(&item.code()).and_then(|details| details.price).unwrap_or_else(|| item.shop_price()));
+    if item.random_shop_price() { price * max_price_modifier } else { price }
+}
+
+// progression in shops must be affordable with the Spirit Light reachable so far, after buying all the progression placed in shops before
+fn spirit_light_left(world_context: &WorldContext) -> f32 {
+    f32::from(world_context.world.player.inventory.get(&Item::SpiritLight(1))) - world_context.progression_spent as f32
+}
+
+// other purchasable slots like Lupo's zone maps keep their vanilla price, so they don't count as shop slots here
+fn can_afford(item: &Item, difficulty: Difficulty, node: &Node, spirit_light_left: f32, custom_items: &HashMap<String, ItemDetails>, max_price_modifier: f32) -> bool {
+    !node.uber_state().map_or(false, UberState::is_shop) ||
+    !item.is_progression(difficulty) ||
+    max_shop_price(item, custom_items, max_price_modifier) <= spirit_light_left
+}
+
 fn progression_check<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &GeneratorContext<'_, '_, R, I>) -> Result<ReachContext<'a>, String>
 where
     R: Rng,
@@ -103,16 +269,22 @@ where
     let mut reachable_states = Vec::new();
     let mut unmet = Vec::new();
 
+    let mut reachable_counts = Vec::new();
+
     for world_context in world_contexts {
-        let (world_reachable, world_unmet) = world_context.world.graph.reached_and_progressions(&world_context.world.player, world_context.spawn, &world_context.world.uber_states, &world_context.world.sets)?;
+        let (world_reachable, world_unmet) = world_context.world.graph.resume_reach(&world_context.world.player, world_context.spawn, &world_context.world.uber_states, &world_context.world.sets, &mut world_context.reach_state)?;
         reachable_states.push(world_reachable.iter().filter(|&&node| !node.can_place()).cloned().collect::<Vec<_>>());
+        // excluded locations are reachable, but don't count towards the reachable locations
+        let reachable_count = world_reachable.iter().filter(|&&node| node.can_place() && !world_context.unreachable_locations.iter().any(|&unreachable| unreachable.index() == node.index())).count();
+        if reachable_count > world_context.reached_count {
+            world_context.reached_count = reachable_count;
+            world_context.spheres += 1;
+        }
+        reachable_counts.push(reachable_count);
         reachable.push(world_reachable);
         unmet.push(world_unmet);
     }
 
-    let reachable_counts = reachable.iter()
-        .map(|world_reachable| world_reachable.iter().filter(|node| node.can_place()).count())
-        .collect::<Vec<_>>();
     let unreached_count = context.total_reachable_count - reachable_counts.iter().sum::<usize>();
 
     Ok(ReachContext {
@@ -124,6 +296,23 @@ where
     })
 }
 
+// How far each world has progressed so far, as the number of spheres it went through
+fn world_progress(world_contexts: &[WorldContext]) -> Vec<f32> {
+    #[allow(clippy::cast_precision_loss)]
+    world_contexts.iter()
+        .map(|world_context| world_context.spheres as f32)
+        .collect()
+}
+
+// How far a world lags behind the average progress of all worlds, from 0 (at or above average) to 1 (nothing reached yet)
+fn world_lag(world_index: usize, progress: &[f32]) -> f32 {
+    #[allow(clippy::cast_precision_loss)]
+    let average = progress.iter().sum::<f32>() / progress.len() as f32;
+    if average > 0.0 {
+        (1.0 - progress[world_index] / average).clamp(0.0, 1.0)
+    } else { 0.0 }
+}
+
 fn place_item<'a, R, I>(origin_world_index: usize, target_world_index: usize, node: &'a Node, was_placeholder: bool, item: Item, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
 where
     R: Rng,
@@ -132,54 +321,75 @@ where
     let origin_player_name = world_contexts[origin_world_index].player_name.clone();
     let target_player_name = world_contexts[target_world_index].player_name.clone();
 
+    if !meets_constraints(&item, origin_world_index, node, context.constraints) {
+        return Err(format!("({}): Placing {} at {} would violate the placement constraints", origin_player_name, item, node));
+    }
+
+    if let Item::SpiritLight(amount) = item {
+        context.spirit_light.placed(target_world_index, amount);
+    }
+
+    let target_difficulty = world_contexts[target_world_index].world.player.difficulty;
+    // messages and custom item details refer to what the randomizer grants, which differs from the logic's item for shuffled teleporters
+    let code = world_contexts[target_world_index].world.graph.seed_item(&item).code();
     let origin_world_context = &mut world_contexts[origin_world_index];
 
     let uber_state = node.uber_state().unwrap();
     let is_shop = uber_state.is_shop();
 
-    let code = item.code();
-    let details = context.custom_items.get(&code);
+    let details = context.custom_items[target_world_index].get(&code);
 
     if uber_state.is_purchasable() {
         origin_world_context.shop_slots -= 1;
+    }
+    // only the slots in SHOP_PRICES get a price, which is what the shop budget and affordability checks work with
+    if is_shop {
+        let (_, _, price_uber_state) = SHOP_PRICES.iter()
+            .find(|(_, location, _)| &uber_state.identifier == location)
+            .ok_or_else(|| format!("({}): Uber State {} claims to be a shop location, but doesn't have an entry in the shop prices table!", origin_player_name, node))?;
+
+        let custom_price = details.and_then(|details| details.price);
+        let mut price = custom_price.unwrap_or_else(|| item.shop_price());
+
+        if item.random_shop_price() {
+            let modified_price = f32::from(price) * context.price_range.sample(context.rng);
+            price = util::float_to_int(modified_price).map_err(|_| format!("({}): Overflowed shop price for {} after adding a random amount to it", origin_player_name, item))?;
+        }
 
-        if is_shop {
-            let (_, _, price_uber_state) = SHOP_PRICES.iter()
-                .find(|(_, location, _)| &uber_state.identifier == location)
-                .ok_or_else(|| format!("({}): Uber State {} claims to be a shop location, but doesn't have an entry in the shop prices table!", origin_player_name, node))?;
+        let price_setter = UberState {
+            identifier: price_uber_state.clone(),
+            value: price.to_string(),
+        }.to_item(UberType::Int);
 
-            let mut price = details.and_then(|details| details.price).unwrap_or_else(|| item.shop_price());
+        log::trace!("({}): Placing {} at Spawn as price for the item below", origin_player_name, price_setter);
 
-            if item.random_shop_price() {
-                let modified_price = f32::from(price) * context.price_range.sample(context.rng);
-                price = util::float_to_int(modified_price).map_err(|_| format!("({}): Overflowed shop price for {} after adding a random amount to it", origin_player_name, item))?;
-            }
+        origin_world_context.shop_prices.push(ShopPrice {
+            placement_index: origin_world_context.placements.len(),
+            uber_identifier: price_uber_state.clone(),
+            price,
+            adjustable: custom_price.is_none() && item.random_shop_price(),
+        });
+        if item.is_progression(target_difficulty) {
+            origin_world_context.progression_spent += u32::from(price);
+        }
 
-            let price_setter = UberState {
-                identifier: price_uber_state.clone(),
-                value: price.to_string(),
-            }.to_item(UberType::Int);
+        origin_world_context.placements.push(Placement {
+            node: None,
+            uber_state: UberState::load(),
+            item: price_setter,
+        });
 
-            log::trace!("({}): Placing {} at Spawn as price for the item below", origin_player_name, price_setter);
+        if let Some(icon) = details.and_then(|details| details.icon.clone()).or_else(|| item.icon()) {
+            let icon_setter = Item::ShopCommand(ShopCommand::SetIcon {
+                uber_state: uber_state.clone(),
+                icon,
+            });
 
             origin_world_context.placements.push(Placement {
                 node: None,
                 uber_state: UberState::load(),
-                item: price_setter,
+                item: icon_setter,
             });
-
-            if let Some(icon) = details.and_then(|details| details.icon.clone()).or_else(|| item.icon()) {
-                let icon_setter = Item::ShopCommand(ShopCommand::SetIcon {
-                    uber_state: uber_state.clone(),
-                    icon,
-                });
-
-                origin_world_context.placements.push(Placement {
-                    node: None,
-                    uber_state: UberState::load(),
-                    item: icon_setter,
-                });
-            }
         }
     }
 
@@ -301,12 +511,7 @@ where
         if placed_keystones < 2 { return Ok(()); }
 
         let required_keystones: u16 = reachable_states[target_world_index].iter()
-            .filter_map(|&node| {
-                if let Some((_, keystones)) = KEYSTONE_DOORS.iter().find(|&&(identifier, _)| identifier == node.identifier()) {
-                    return Some(*keystones);
-                }
-                None
-            })
+            .filter_map(|&node| world_context.world.graph.keystone_cost(node))
             .sum();
         if required_keystones <= placed_keystones { return Ok(()); }
 
@@ -328,6 +533,11 @@ where
     I: Iterator<Item=usize>,
 {
     let is_multiworld_spread = item.is_multiworld_spread();
+    let constraints = context.constraints;
+    let custom_items = &context.custom_items[target_world_index];
+    let max_price_modifier = context.max_price_modifier;
+    let difficulty = world_contexts[target_world_index].world.player.difficulty;
+    let spirit_light_available = world_contexts.iter().map(spirit_light_left).collect::<Vec<_>>();
 
     let mut choose_node = || {
         if is_multiworld_spread {
@@ -364,18 +574,19 @@ where
     let mut node = choose_node()?;
 
     // Don't place Spirit Light in shops
-    if matches!(item, Item::SpiritLight(_)) {
-        let mut skipped_slots = Vec::new();
+    let is_spirit_light = matches!(item, Item::SpiritLight(_));
+    let mut skipped_slots = Vec::new();
 
-        while node.1.uber_state().unwrap().is_purchasable() {
-            skipped_slots.push((node.0, node.1));
+    while (is_spirit_light && node.1.uber_state().unwrap().is_purchasable())
+    || !meets_constraints(&item, node.0, node.1, constraints)
+    || !can_afford(&item, difficulty, node.1, spirit_light_available[node.0], custom_items, max_price_modifier) {
+        skipped_slots.push((node.0, node.1));
 
-            node = choose_node()?;
-        }
+        node = choose_node()?;
+    }
 
-        for skipped_slot in skipped_slots {
-            world_contexts[skipped_slot.0].placeholders.push(skipped_slot.1);
-        }
+    for skipped_slot in skipped_slots {
+        world_contexts[skipped_slot.0].placeholders.push(skipped_slot.1);
     }
 
     let world_context = &mut world_contexts[target_world_index];
@@ -405,7 +616,7 @@ fn determine_progressions<'a>(world_index: usize, slots: usize, world_slots: usi
                 for missing in orb_variants {
                     // log::trace!("missing items: {}", missing);
 
-                    if missing.items.is_empty() {  // sanity check
+                    if missing.is_empty() {  // sanity check
                         log::trace!("({}): Failed to determine which items were needed for progression to meet {:?} (had {})", world_context.player_name, requirement, world_context.world.player.inventory);
                         return Err(String::from("Failed to determine which items were needed for progression"));
                     }
@@ -436,14 +647,26 @@ fn filter_itemsets(itemsets: &mut Vec<Inventory>) {
     }
 }
 
-fn pick_progression<'a, 'b, R, I>(target_world_index: usize, itemsets: &'b [Inventory], slots: usize, reach_context: &ReachContext, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<&'b Inventory, String>where
+fn pick_progression<'a, R, I>(target_world_index: usize, itemsets: &[Inventory], slots: usize, reach_context: &ReachContext, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<Inventory, String>where
 R: Rng,
 I: Iterator<Item=usize>,
 {
     let world_context = &world_contexts[target_world_index];
     log::trace!("({}): {} options for forced progression:", world_context.player_name, itemsets.len());
 
-    let weight = |inventory: &Inventory| -> Result<f32, String> {
+    // a world that fell behind the others prefers progressions that open up more of it at once
+    let balancing_exponent = if context.progression_balancing > 0.0 {
+        1.0 + context.progression_balancing * world_lag(target_world_index, &world_progress(world_contexts))
+    } else { 1.0 };
+
+    // traps that already exist can't be helped anymore, only new ones are worth avoiding
+    let known_traps = if context.avoid_softlocks {
+        let target_world_context = &world_contexts[target_world_index];
+        target_world_context.world.graph.traps(&target_world_context.world.player, target_world_context.spawn, &target_world_context.world.uber_states, &target_world_context.world.sets)?
+    } else { Vec::new() };
+
+    // progressions which could softlock are left out entirely if avoid_softlocks is passed, returning None
+    let weight = |inventory: &Inventory, avoid_softlocks: bool| -> Result<Option<f32>, String> {
         let mut newly_reached = 0;
 
         let target_world_context = &world_contexts[target_world_index];
@@ -452,7 +675,8 @@ I: Iterator<Item=usize>,
             inventory: target_world_context.world.player.inventory.merge(inventory),
             ..target_world_context.world.player.clone()
         };
-        let mut lookahead_reachable = target_world_context.world.graph.reached_locations(&lookahead_player, target_world_context.spawn, &target_world_context.world.uber_states, &target_world_context.world.sets)?;
+        let mut lookahead_state = target_world_context.reach_state.clone();
+        let (mut lookahead_reachable, _) = target_world_context.world.graph.resume_reach(&lookahead_player, target_world_context.spawn, &target_world_context.world.uber_states, &target_world_context.world.sets, &mut lookahead_state)?;
         lookahead_reachable.retain(|&node| node.can_place());
 
         newly_reached += lookahead_reachable.len().saturating_sub(reach_context.reachable_counts[target_world_index]);
@@ -463,32 +687,60 @@ I: Iterator<Item=usize>,
         let preplaced_reached = lookahead_reachable.len();
 
         if slots - inventory.item_count() < 3 && newly_reached <= preplaced_reached {
-            return Ok(0.000001);
+            return Ok(Some(0.000001));
+        }
+        if avoid_softlocks {
+            let traps = target_world_context.world.graph.traps(&lookahead_player, target_world_context.spawn, &target_world_context.world.uber_states, &target_world_context.world.sets)?;
+            if let Some(trap) = traps.iter().find(|trap| !known_traps.iter().any(|known| known.index() == trap.index())) {
+                log::trace!("({}): {} could softlock in {}", target_world_context.player_name, inventory, trap);
+                return Ok(None);
+            }
         }
 
         let base_weight = 1.0 / inventory.cost();
 
         #[allow(clippy::cast_precision_loss)]
-        Ok(base_weight * (newly_reached + 1) as f32)
+        Ok(Some(base_weight * ((newly_reached + 1) as f32).powf(balancing_exponent)))
+    };
+    let weigh = |inventories: Vec<Inventory>, avoid_softlocks: bool| -> Result<Vec<(Inventory, f32)>, String> {
+        inventories.into_iter()
+            .filter_map(|inventory| weight(&inventory, avoid_softlocks).map(|weight| weight.map(|weight| (inventory, weight))).transpose())
+            .collect()
     };
-    let with_weights = itemsets.iter()
-        .map::<Result<(&Inventory, f32), String>, _>(|inventory| Ok((inventory, weight(inventory)?)))
-        .collect::<Result<Vec<_>, _>>()?;
+    let mut with_weights = weigh(itemsets.to_vec(), context.avoid_softlocks)?;
+
+    // a progression which could softlock on its own may be safe along with another one leading back out
+    if with_weights.is_empty() && context.avoid_softlocks {
+        let pool = &world_context.world.pool;
+        let combined = itemsets.iter().enumerate()
+            .flat_map(|(index, inventory)| itemsets[index + 1..].iter().map(move |other| inventory.merge(other)))
+            .filter(|inventory| inventory.item_count() <= slots && pool.contains(inventory))
+            .collect::<Vec<_>>();
+        log::trace!("({}): Every option for forced progression could softlock, trying {} combinations of them", world_context.player_name, combined.len());
+
+        with_weights = weigh(combined, true)?;
+    }
+    // the logic has plenty of one-way paths, failing the attempt here would fail most seeds
+    if with_weights.is_empty() && context.avoid_softlocks {
+        log::trace!("({}): Every option for forced progression could softlock, choosing among all of them", world_context.player_name);
+
+        with_weights = weigh(itemsets.to_vec(), false)?;
+    }
     let weight_sum: f32 = with_weights.iter().map(|(_, weight)| weight).sum();
 
     let (progression, _) = with_weights
-        .choose_weighted(context.rng, |&(inventory, weight)| {
+        .choose_weighted(context.rng, |(inventory, weight)| {
             let mut inventory = format!("{}", inventory);
             util::add_trailing_spaces(&mut inventory, 20);
             log::trace!("-> {}  ({}%)", inventory, (weight / weight_sum * 1000.0).round() / 10.0);
 
-            weight
+            *weight
         })
         .map_err(|err| format!("({}): Error choosing progression: {}", world_context.player_name, err))?;
 
     log::trace!("({}): Chosen progression: {}", world_context.player_name, progression);
 
-    Ok(progression)
+    Ok(progression.clone())
 }
 
 fn split_progression_item<'a, R, I>(world_index: usize, item: &Item, amount: &u16, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<Vec<Item>, String>
@@ -501,7 +753,10 @@ where
         let mut amount_placed = 0;
 
         while &amount_placed < amount {
-            let stacked_amount = world_contexts[world_index].spirit_light_rng.sample(context.rng);
+            let stacked_amount = context.spirit_light.sample(world_index, context.rng);
+            if stacked_amount == 0 {
+                return Err(format!("({}): Ran out of Spirit Light to place as progression", world_contexts[world_index].player_name));
+            }
             amount_placed += stacked_amount;
             spirit_light_items.push(Item::SpiritLight(stacked_amount));
         }
@@ -533,6 +788,9 @@ where
             let world_context = &world_contexts[world_index];
 
             let mut itemsets = determine_progressions(world_index, available_spawn_slots, available_spawn_slots, &reach_context, world_context)?;
+            if let Some(&spawn_slot) = world_context.spawn_slots.last() {
+                itemsets.retain(|itemset| itemset.iter().all(|(item, _)| meets_constraints(&item, world_index, spawn_slot, context.constraints)));
+            }
 
             if itemsets.is_empty() {
                 log::trace!("({}): No progressions found", world_context.player_name);
@@ -542,8 +800,8 @@ where
             filter_itemsets(&mut itemsets);
             let progression = pick_progression(world_index, &itemsets, available_spawn_slots, &reach_context, world_contexts, context)?;
 
-            for (item, amount) in &progression.items {
-                let items = split_progression_item(world_index, item, amount, world_contexts, context)?;
+            for (item, amount) in progression.iter() {
+                let items = split_progression_item(world_index, &item, &amount, world_contexts, context)?;
 
                 for item in items {
                     let world_context = &mut world_contexts[world_index];
@@ -578,6 +836,13 @@ where
     let mut world_indices = (0..context.world_count).collect::<Vec<_>>();
     world_indices.shuffle(context.rng);
 
+    // with balancing, the world that went through the fewest spheres so far gets the first chance at progression
+    if context.world_count > 1 && context.progression_balancing > 0.0 && context.rng.gen_bool(f64::from(context.progression_balancing)) {
+        let progress = world_progress(world_contexts);
+        log::trace!("Balancing progression, world spheres: {}", progress.iter().map(f32::to_string).collect::<Vec<_>>().join(", "));
+        world_indices.sort_by(|&a, &b| progress[b].partial_cmp(&progress[a]).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
     let (target_world_index, mut itemsets) = loop {
         if let Some(chosen_world_index) = world_indices.pop() {
             let world_context = &mut world_contexts[chosen_world_index];
@@ -592,7 +857,7 @@ where
             }
         } else {
             if world_contexts.iter().any(|world_context| !world_context.placeholders.is_empty()) &&
-            world_contexts.iter().any(|world_context| world_context.world.pool.inventory.items.iter().any(|(item, _)| matches!(item, Item::UberState(_)))) {
+            world_contexts.iter().any(|world_context| world_context.world.pool.inventory.iter().any(|(item, _)| matches!(item, Item::UberState(_)))) {
                 flush_item_pool(world_contexts, context)?;
                 return Ok(());
             }
@@ -631,8 +896,8 @@ where
     filter_itemsets(&mut itemsets);
     let progression = pick_progression(target_world_index, &itemsets, slots, reach_context, world_contexts, context)?;
 
-    for (item, amount) in &progression.items {
-        let items = split_progression_item(target_world_index, item, amount, world_contexts, context)?;
+    for (item, amount) in progression.iter() {
+        let items = split_progression_item(target_world_index, &item, &amount, world_contexts, context)?;
 
         for item in items {
             forced_placement(target_world_index, item, reserved_slots, world_contexts, context)?;
@@ -656,10 +921,18 @@ where
         let target_world_index = context.rng.gen_range(0..context.world_count);
 
         if is_purchasable || origin_world_context.shop_slots < world_contexts[target_world_index].world.pool.inventory.item_count() {
+            let spirit_light_left = spirit_light_left(&world_contexts[origin_world_index]);
             let target_world_context = &mut world_contexts[target_world_index];
 
-            if let Some(item) = target_world_context.world.pool.choose_random(origin_world_index != target_world_index, context.rng) {
-                let item = item.clone();
+            let constraints = context.constraints;
+            let custom_items = &context.custom_items[target_world_index];
+            let max_price_modifier = context.max_price_modifier;
+            let target_difficulty = target_world_context.world.player.difficulty;
+            let allowed = |item: &Item|
+                meets_constraints(item, origin_world_index, node, constraints) &&
+                can_afford(item, target_difficulty, node, spirit_light_left, custom_items, max_price_modifier);
+
+            if let Some(item) = target_world_context.world.pool.choose_random(origin_world_index != target_world_index, allowed, context.rng) {
                 target_world_context.world.pool.remove(&item, 1);
                 target_world_context.world.grant_player(item.clone(), 1).unwrap_or_else(|err| log::error!("({}): {}", target_world_context.player_name, err));
                 place_item(origin_world_index, target_world_index, node, false, item, world_contexts, context)?;
@@ -673,7 +946,12 @@ where
 
     let origin_world_context = &mut world_contexts[origin_world_index];
 
-    let amount = origin_world_context.spirit_light_rng.sample(context.rng);
+    let amount = context.spirit_light.sample(origin_world_index, context.rng);
+    if amount == 0 {
+        log::trace!("({}): No Spirit Light left to place, reserving {} as placeholder", origin_world_context.player_name, node);
+        origin_world_context.placeholders.push(node);
+        return Ok(false);
+    }
     let item = Item::SpiritLight(amount);
 
     origin_world_context.world.pool.remove(&item, 1);
@@ -772,6 +1050,61 @@ impl SpiritLightAmounts {
     }
 }
 
+/// Hands out the Spirit Light amounts for all worlds
+///
+/// Spirit Light is counted towards the world receiving it, no matter which world it was placed in, so every world's total ends up matching its pool.
+struct SpiritLightDistributor {
+    amounts: Vec<SpiritLightAmounts>,
+    remaining: Vec<u32>,
+}
+impl SpiritLightDistributor {
+    fn new(amounts: Vec<SpiritLightAmounts>, pools: &[u16]) -> SpiritLightDistributor {
+        SpiritLightDistributor {
+            amounts,
+            remaining: pools.iter().copied().map(u32::from).collect(),
+        }
+    }
+    /// Roll the next Spirit Light amount for the target world, never exceeding what is left of its pool
+    fn sample<R>(&mut self, target_world_index: usize, rng: &mut R) -> u16
+    where
+        R: Rng + ?Sized
+    {
+        let amount = self.amounts[target_world_index].sample(rng);
+        u16::try_from(self.remaining[target_world_index]).map_or(amount, |remaining| amount.min(remaining))
+    }
+    fn placed(&mut self, target_world_index: usize, amount: u16) {
+        let remaining = &mut self.remaining[target_world_index];
+        *remaining = remaining.saturating_sub(u32::from(amount));
+    }
+    /// Split whatever is left of the target world's pool into amounts for up to the given number of slots
+    ///
+    /// The amounts keep the shape of the usual distribution, but are scaled so that they add up exactly.
+    /// Every amount is at least 1, so if less Spirit Light than slots is left, fewer amounts are returned
+    fn distribute_remaining<R>(&mut self, target_world_index: usize, slots: usize, rng: &mut R) -> Result<Vec<u16>, String>
+    where
+        R: Rng + ?Sized
+    {
+        let remaining = self.remaining[target_world_index];
+        let slots = slots.min(remaining as usize);
+        if slots == 0 { return Ok(Vec::new()); }
+
+        let rolls = (0..slots).map(|_| u32::from(self.amounts[target_world_index].sample(rng)).max(1)).collect::<Vec<_>>();
+        let roll_total: u64 = rolls.iter().copied().map(u64::from).sum();
+        #[allow(clippy::cast_possible_truncation)]
+        let spread = remaining - slots as u32;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let mut amounts = rolls.iter().map(|&roll| 1 + (u64::from(roll) * u64::from(spread) / roll_total) as u32).collect::<Vec<_>>();
+        // rounding down leaves less than one Spirit Light per slot, add it to the last slots
+        let leftover = remaining - amounts.iter().sum::<u32>();
+        for amount in amounts.iter_mut().rev().take(leftover as usize) {
+            *amount += 1;
+        }
+
+        amounts.into_iter().map(|amount| u16::try_from(amount).map_err(|_| format!("Can't split {} Spirit Light into {} pickups without exceeding the maximum amount per pickup", remaining, slots))).collect()
+    }
+}
+
 fn place_remaining<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
 where
     R: Rng,
@@ -795,12 +1128,14 @@ where
     }
 
     for target_world_index in 0..context.world_count {
-        let mut remaining = world_contexts[target_world_index].world.pool.inventory.items.drain()
+        let mut remaining = std::mem::take(&mut world_contexts[target_world_index].world.pool.inventory).iter()
             .flat_map(|(item, amount)| vec![item; amount.into()])
             .collect::<Vec<_>>();
         log::trace!("({}): Placing the remaining {} items randomly", world_contexts[target_world_index].player_name, remaining.len());
 
         remaining.shuffle(context.rng);
+        // constrained items have fewer options, so place them first
+        remaining.sort_by_key(|item| !context.constraints.iter().any(|(constrained, _)| constrained == item));
 
         let mut out_of_space = false;
         'outer: for item in remaining {
@@ -813,13 +1148,19 @@ where
                     vec![target_world_index]
                 };
 
-                for origin_world_index in origin_world_indices {
-                    if let Some(node) = shop_placeholders[origin_world_index].pop().or_else(|| world_contexts[origin_world_index].placeholders.pop()) {
+                for &origin_world_index in &origin_world_indices {
+                    if let Some(node) = take_allowed(&mut shop_placeholders[origin_world_index], &item, origin_world_index, context.constraints)
+                        .or_else(|| take_allowed(&mut world_contexts[origin_world_index].placeholders, &item, origin_world_index, context.constraints))
+                    {
                         place_item(origin_world_index, target_world_index, node, true, item, world_contexts, context)?;
                         continue 'outer;
                     }
                 }
 
+                if origin_world_indices.iter().any(|&origin_world_index| !shop_placeholders[origin_world_index].is_empty() || !world_contexts[origin_world_index].placeholders.is_empty()) {
+                    return Err(format!("({}): Not enough slots to place {} without violating the placement constraints", world_contexts[target_world_index].player_name, item));
+                }
+
                 out_of_space = true;
 
                 log::warn!("({}): Not enough space to place all items from the item pool!", world_contexts[target_world_index].player_name);
@@ -845,9 +1186,26 @@ where
     for world_index in 0..context.world_count {
         log::trace!("({}): Placed all items from the pool, placing Spirit Light", world_contexts[world_index].player_name);
 
+        let world_context = &mut world_contexts[world_index];
+        let placements = &world_context.placements;
+        world_context.unreachable_locations.retain(|&unreachable| {
+            let uber_state = unreachable.uber_state().unwrap();
+            !placements.iter().any(|placement| &placement.uber_state == uber_state)  // 1xp may have been placed here
+        });
+        let spirit_light_slots = world_context.placeholders.len() + world_context.unreachable_locations.iter().filter(|&node| !node.uber_state().unwrap().is_purchasable()).count();
+
+        if spirit_light_slots == 0 && context.spirit_light.remaining[world_index] > 0 {
+            log::warn!("({}): Not enough space to place all Spirit Light from the item pool!", world_context.player_name);
+        }
+        let mut amounts = context.spirit_light.distribute_remaining(world_index, spirit_light_slots, context.rng)
+            .map_err(|err| format!("({}): {}", world_contexts[world_index].player_name, err))?.into_iter();
+        if amounts.len() < spirit_light_slots {
+            log::warn!("({}): Not enough Spirit Light left to fill all locations! Filling with extra Gorlek Ore", world_contexts[world_index].player_name);
+        }
+
+        // once the Spirit Light runs out, the remaining slots get the same filler as empty shops
         while let Some(placeholder) = world_contexts[world_index].placeholders.pop() {
-            let amount = world_contexts[world_index].spirit_light_rng.sample(context.rng);
-            let item = Item::SpiritLight(amount);
+            let item = amounts.next().map_or(Item::Resource(Resource::Ore), Item::SpiritLight);
 
             place_item(world_index, world_index, placeholder, true, item, world_contexts, context)?;
         }
@@ -856,11 +1214,10 @@ where
             log::trace!("({}): Filling unreachable locations", world_contexts[world_index].player_name);
         }
         while let Some(unreachable) = world_contexts[world_index].unreachable_locations.pop() {
-            let item = if unreachable.uber_state().map_or(false, UberState::is_purchasable) {
+            let item = if unreachable.uber_state().unwrap().is_purchasable() {
                 Item::Resource(Resource::Ore)
             } else {
-                let amount = world_contexts[world_index].spirit_light_rng.sample(context.rng);
-                Item::SpiritLight(amount)
+                amounts.next().map_or(Item::Resource(Resource::Ore), Item::SpiritLight)
             };
 
             place_item(world_index, world_index, unreachable, false, item, world_contexts, context)?;
@@ -872,13 +1229,37 @@ where
     Ok(())
 }
 
+fn apply_shop_budget(budget: u32, world_context: &mut WorldContext) -> Result<(), String> {
+    let total: u32 = world_context.shop_prices.iter().map(|shop_price| u32::from(shop_price.price)).sum();
+    if total <= budget { return Ok(()); }
+
+    let fixed: u32 = world_context.shop_prices.iter().filter(|shop_price| !shop_price.adjustable).map(|shop_price| u32::from(shop_price.price)).sum();
+    if fixed > budget {
+        return Err(format!("({}): Shop prices which can't be changed already cost {} Spirit Light, which exceeds the shop budget of {}", world_context.player_name, fixed, budget));
+    }
+
+    // prices only go down, so progression in shops stays affordable
+    let factor = (budget - fixed) as f32 / (total - fixed) as f32;
+    log::trace!("({}): Scaling shop prices by {} to fit into the shop budget of {} (was {})", world_context.player_name, factor, budget, total);
+
+    for shop_price in world_context.shop_prices.iter_mut().filter(|shop_price| shop_price.adjustable) {
+        shop_price.price = util::float_to_int(f32::from(shop_price.price) * factor)?;
+        world_context.placements[shop_price.placement_index].item = UberState {
+            identifier: shop_price.uber_identifier.clone(),
+            value: shop_price.price.to_string(),
+        }.to_item(UberType::Int);
+    }
+
+    Ok(())
+}
+
 #[inline]
 fn total_reach_check<'a>(world: &World<'a>, player_name: &str) -> Result<Vec<&'a Node>, String> {
     log::trace!("({}): Creating a player with everything to determine reachable locations", player_name);
     let mut finished_world = world.clone();
-    for (item, amount) in &world.pool.inventory.items {
+    for (item, amount) in world.pool.inventory.iter() {
         if item.is_progression(world.player.difficulty) {
-            finished_world.grant_player(item.clone(), *amount)?;
+            finished_world.grant_player(item, amount)?;
         }
     }
     finished_world.grant_player(Item::SpiritLight(1), world.pool.spirit_light)?;
@@ -895,7 +1276,6 @@ fn total_reach_check<'a>(world: &World<'a>, player_name: &str) -> Result<Vec<&'a
         if new_reachable_count > total_reachable_count {
             total_reachable_count = new_reachable_count;
         } else {
-            reachable_locations.retain(|&node| node.can_place());
             return Ok(reachable_locations);
         }
 
@@ -921,9 +1301,9 @@ where
     log::trace!("Got stuck. Trying to flush uberState items from the item pool to recover...");
 
     for target_world_index in 0..context.world_count {
-        let uber_state_items = world_contexts[target_world_index].world.pool.inventory.items.iter()
+        let uber_state_items = world_contexts[target_world_index].world.pool.inventory.iter()
             .filter(|(item, _)| matches!(item, Item::UberState(_)))
-            .flat_map(|(item, amount)| vec![item.clone(); (*amount).into()])
+            .flat_map(|(item, amount)| vec![item; amount.into()])
             .collect::<Vec<_>>();
 
         'outer: for item in uber_state_items {
@@ -931,7 +1311,7 @@ where
             origin_world_indices.shuffle(context.rng);
 
             for origin_world_index in origin_world_indices {
-                if let Some(node) = world_contexts[origin_world_index].placeholders.pop() {
+                if let Some(node) = take_allowed(&mut world_contexts[origin_world_index].placeholders, &item, origin_world_index, context.constraints) {
                     let target_world_context = &mut world_contexts[target_world_index];
 
                     target_world_context.world.pool.remove(&item, 1);
@@ -951,15 +1331,16 @@ pub fn generate_placements<'a, R>(
     worlds: Vec<World<'a>>,
     spawns: &[&'a Node],
     spawn_pickup_node: &'a Node,
-    custom_items: &HashMap<String, ItemDetails>,
+    custom_items: &[HashMap<String, ItemDetails>],
+    constraints: &[(Item, Constraint)],
     settings: &Settings,
     rng: &mut R
 ) -> Result<Vec<Vec<Placement<'a>>>, String>
 where
     R: Rng,
 {
-    // TODO enforce a max total price for shops
-    let price_range = Uniform::new_inclusive(0.75, 1.25);
+    let (min_price_modifier, max_price_modifier) = settings.generator.price_range;
+    let price_range = Uniform::new_inclusive(min_price_modifier, max_price_modifier);
     let world_tour = settings.goalmodes.iter().find_map(|goalmode|
         match *goalmode {
             GoalMode::Relics(amount) => Some(amount),
@@ -978,7 +1359,7 @@ where
 
     let mut has_warned_about_tp_refill = false;
 
-    let mut world_contexts = worlds.into_iter().enumerate().map(|(world_index, mut world)| {
+    let (mut world_contexts, spirit_light_amounts): (Vec<_>, Vec<_>) = worlds.into_iter().enumerate().map(|(world_index, mut world)| {
         let player_name = settings.players.get(world_index).cloned().unwrap_or_else(|| format!("Player {}", world_index + 1));
 
         world.collect_preplacements(&UberState::spawn());
@@ -1002,33 +1383,35 @@ where
         let mut spawn_is_tp = false;
         // Remove spawn tp from the pool
         if let Some(spawn_tp) = match spawn_identifier {
-            "MarshSpawn.Main" => Some(Item::Teleporter(Teleporter::Marsh)),
-            "HowlsDen.Teleporter" => Some(Item::Teleporter(Teleporter::Den)),
-            "EastHollow.Teleporter" => Some(Item::Teleporter(Teleporter::Hollow)),
-            "GladesTown.Teleporter" => Some(Item::Teleporter(Teleporter::Glades)),
-            "InnerWellspring.Teleporter" => Some(Item::Teleporter(Teleporter::Wellspring)),
-            "MidnightBurrows.Teleporter" => Some(Item::Teleporter(Teleporter::Burrows)),
-            "WoodsEntry.Teleporter" => Some(Item::Teleporter(Teleporter::WestWoods)),
-            "WoodsMain.Teleporter" => Some(Item::Teleporter(Teleporter::EastWoods)),
-            "LowerReach.Teleporter" => Some(Item::Teleporter(Teleporter::Reach)),
-            "UpperDepths.Teleporter" => Some(Item::Teleporter(Teleporter::Depths)),
-            "EastPools.Teleporter" => Some(Item::Teleporter(Teleporter::EastLuma)),
-            "WestPools" => Some(Item::Teleporter(Teleporter::WestLuma)),
-            "LowerWastes.WestTP" => Some(Item::Teleporter(Teleporter::WestWastes)),
-            "LowerWastes.EastTP" => Some(Item::Teleporter(Teleporter::EastWastes)),
-            "UpperWastes.NorthTP" => Some(Item::Teleporter(Teleporter::OuterRuins)),
-            "WindtornRuins.RuinsTP" => Some(Item::Teleporter(Teleporter::InnerRuins)),
-            "WillowsEnd.InnerTP" => Some(Item::Teleporter(Teleporter::Willow)),
+            "MarshSpawn.Main" => Some(Teleporter::Marsh),
+            "HowlsDen.Teleporter" => Some(Teleporter::Den),
+            "EastHollow.Teleporter" => Some(Teleporter::Hollow),
+            "GladesTown.Teleporter" => Some(Teleporter::Glades),
+            "InnerWellspring.Teleporter" => Some(Teleporter::Wellspring),
+            "MidnightBurrows.Teleporter" => Some(Teleporter::Burrows),
+            "WoodsEntry.Teleporter" => Some(Teleporter::WestWoods),
+            "WoodsMain.Teleporter" => Some(Teleporter::EastWoods),
+            "LowerReach.Teleporter" => Some(Teleporter::Reach),
+            "UpperDepths.Teleporter" => Some(Teleporter::Depths),
+            "EastPools.Teleporter" => Some(Teleporter::EastLuma),
+            "WestPools" => Some(Teleporter::WestLuma),
+            "LowerWastes.WestTP" => Some(Teleporter::WestWastes),
+            "LowerWastes.EastTP" => Some(Teleporter::EastWastes),
+            "UpperWastes.NorthTP" => Some(Teleporter::OuterRuins),
+            "WindtornRuins.RuinsTP" => Some(Teleporter::InnerRuins),
+            "WillowsEnd.InnerTP" => Some(Teleporter::Willow),
             _ => None,
         } {
             spawn_is_tp = true;
+            // with shuffled teleporters, a different teleporter leads to the spawn
+            let spawn_tp = Item::Teleporter(world.graph.teleporter_access(spawn_tp));
             world.pool.inventory.remove(&spawn_tp, 1);
         }
 
         let spawn = spawns[world_index];
         // Add a teleport icon for fully random spawn
         if !spawn_is_tp {
-            if !has_warned_about_tp_refill && !settings.header_list.iter().any(|header|
+            if !has_warned_about_tp_refill && !settings.for_world(world_index).header_list.iter().any(|header|
                 header.file_stem().map_or(false, |stem|
                     stem.to_str().map_or(false, |stem|
                         stem == "tp_refill"
@@ -1047,9 +1430,35 @@ where
             });
         }
 
-        let reachable_locations = total_reach_check(&world, &player_name)?;
+        // tell the randomizer how many keystones each door costs if they have been randomized
+        for &(door_index, cost) in &world.graph.keystone_doors {
+            let door = &world.graph.nodes[door_index];
+            let &(_, _, cost_id) = KEYSTONE_DOORS.iter().find(|&&(identifier, ..)| identifier == door.identifier())
+                .ok_or_else(|| format!("({}): {} claims to be a keystone door, but doesn't have an entry in the keystone doors table!", player_name, door))?;
+
+            placements.push(Placement {
+                node: None,
+                uber_state: UberState::load(),
+                item: UberState {
+                    identifier: UberIdentifier { uber_group: KEYSTONE_COST_GROUP, uber_id: cost_id },
+                    value: cost.to_string(),
+                }.to_item(UberType::Int),
+            });
+        }
+
+        // tell the randomizer where shuffled doors lead
+        for &DoorRedirect { intercept, set } in &world.graph.door_redirects {
+            placements.push(Placement {
+                node: None,
+                uber_state: UberState::load(),
+                item: Item::Command(Command::StateRedirect { intercept, set }),
+            });
+        }
+
+        let mut reachable_locations = total_reach_check(&world, &player_name)?;
+        reachable_locations.retain(|&node| node.can_place());
 
-        let unreachable_locations = world.graph.nodes.iter()
+        let mut unreachable_locations = world.graph.nodes.iter()
             .filter(|&node|
                 node.can_place() &&
                 !reachable_locations.iter().any(|&reachable| reachable.index() == node.index()) &&
@@ -1057,12 +1466,27 @@ where
             ).collect::<Vec<_>>();
         if !unreachable_locations.is_empty() {
             let identifiers = unreachable_locations.iter().map(|&node| node.identifier()).collect::<Vec<_>>();
-            if !(unreachable_locations.len() == 1 && settings.difficulty == Difficulty::Moki) {  // moki always has one unreachable pickup
+            if !(unreachable_locations.len() == 1 && world.player.difficulty == Difficulty::Moki) {  // moki always has one unreachable pickup
                 log::warn!("({}): {} locations are unreachable on these settings! These will only hold Spirit Light.", player_name, identifiers.len());
             }
             log::trace!("({}): Unreachable locations on these settings: {}", player_name, format_identifiers(identifiers));
         }
 
+        let excluded_locations = world.graph.nodes.iter()
+            .filter(|&node|
+                node.can_place() &&
+                settings.exclude.iter().any(|excluded| is_excluded(node, excluded)) &&
+                !unreachable_locations.iter().any(|&unreachable| unreachable.index() == node.index()) &&
+                !world.preplacements.contains_key(node.uber_state().unwrap())
+            ).collect::<Vec<_>>();
+        if !excluded_locations.is_empty() {
+            let identifiers = excluded_locations.iter().map(|&node| node.identifier()).collect::<Vec<_>>();
+            log::trace!("({}): Excluded locations: {}", player_name, format_identifiers(identifiers));
+
+            reachable_locations.retain(|&node| !excluded_locations.iter().any(|&excluded| excluded.index() == node.index()));
+            unreachable_locations.extend(excluded_locations);
+        }
+
         let world_slots = world.graph.nodes.iter()
             .filter(|&node| {
                 node.can_place() &&
@@ -1073,7 +1497,8 @@ where
         if let Some(amount) = world_tour { spirit_light_slots -= amount; }
         log::trace!("({}): Estimated {}/{} slots for Spirit Light", player_name, spirit_light_slots, world_slots);
 
-        let spirit_light_rng = SpiritLightAmounts::new(f32::from(world.pool.spirit_light), spirit_light_slots as f32, 0.75, 1.25);
+        let (random_low, random_high) = settings.generator.spirit_light_range;
+        let spirit_light_amounts = SpiritLightAmounts::new(f32::from(world.pool.spirit_light), spirit_light_slots as f32, random_low, random_high);
         let random_spirit_light = Bernoulli::new(spirit_light_slots as f64 / world_slots as f64).unwrap();
 
         let shop_slots = world.graph.nodes.iter().filter(|&node|
@@ -1082,7 +1507,7 @@ where
                 && !world.preplacements.contains_key(uber_state)
         )).count();
 
-        Ok(WorldContext {
+        Ok((WorldContext {
             world,
             player_name,
             spawn,
@@ -1092,11 +1517,17 @@ where
             spawn_slots,
             reachable_locations,
             unreachable_locations,
-            spirit_light_rng,
             random_spirit_light,
             shop_slots,
-        })
-    }).collect::<Result<Vec<_>, String>>()?;
+            shop_prices: Vec::new(),
+            progression_spent: 0,
+            reach_state: None,
+            reached_count: 0,
+            spheres: 0,
+        }, spirit_light_amounts))
+    }).collect::<Result<Vec<_>, String>>()?.into_iter().unzip();
+
+    let spirit_light_pools = world_contexts.iter().map(|world_context| world_context.world.pool.spirit_light).collect::<Vec<_>>();
 
     let total_reachable_count: usize = world_contexts.iter().map(|world_context| world_context.reachable_locations.len()).sum();
 
@@ -1104,9 +1535,14 @@ where
         world_count: settings.worlds,
         total_reachable_count,
         custom_items,
+        constraints,
         multiworld_state_index: 0..,
         price_range,
-        random_progression: Bernoulli::new(RANDOM_PROGRESSION).unwrap(),
+        max_price_modifier,
+        random_progression: Bernoulli::new(settings.generator.random_progression).unwrap(),
+        progression_balancing: settings.generator.progression_balancing,
+        avoid_softlocks: settings.generator.avoid_softlocks,
+        spirit_light: SpiritLightDistributor::new(spirit_light_amounts, &spirit_light_pools),
         rng,
     };
 
@@ -1117,7 +1553,8 @@ where
 
     spawn_progressions(&mut world_contexts, &mut context)?;
 
-    let mut reserved_slots = Vec::with_capacity(RESERVE_SLOTS);
+    let GeneratorConfig { reserve_slots, placeholder_slots, .. } = settings.generator;
+    let mut reserved_slots = Vec::with_capacity(reserve_slots);
 
     loop {
         let mut reach_context = progression_check(&mut world_contexts, &context)?;
@@ -1135,6 +1572,7 @@ where
                     !world_context.placements.iter().any(|placement| &placement.uber_state == uber_state) &&
                     !world_context.placeholders.iter().any(|&placeholder| placeholder.index() == node_index) &&
                     !world_context.collected_preplacements.iter().any(|&collected| collected == node_index) &&
+                    !world_context.unreachable_locations.iter().any(|&unreachable| unreachable.index() == node_index) &&
                     !reserved_slots.iter().any(|&(reserved_world, node)| reserved_world == world_index && node.index() == node_index)
                 )
             });
@@ -1168,14 +1606,14 @@ where
         }).collect::<Vec<_>>();
 
         let all_reached = reach_context.unreached_count == 0;
-        if !all_reached && reserved_slots.len() < RESERVE_SLOTS {
+        if !all_reached && reserved_slots.len() < reserve_slots {
             loop {
                 let world_index = context.rng.gen_range(0..context.world_count);
 
                 if let Some(node) = needs_placement[world_index].pop() {
                     reserved_slots.push((world_index, node));
 
-                    if reserved_slots.len() == RESERVE_SLOTS {
+                    if reserved_slots.len() == reserve_slots {
                         break;
                     }
                 } else {
@@ -1196,7 +1634,7 @@ where
 
             for (origin_world_index, world_needs_placement) in needs_placement.iter().enumerate() {
                 for &node in world_needs_placement {
-                    let allow_placeholder = total_placeholders < PLACEHOLDER_SLOTS;
+                    let allow_placeholder = total_placeholders < placeholder_slots;
                     if !random_placement(origin_world_index, node, allow_placeholder, &mut world_contexts, &mut context)? {
                         total_placeholders += 1;
                     }
@@ -1213,6 +1651,12 @@ where
 
             place_remaining(&mut world_contexts, &mut context)?;
 
+            if let Some(budget) = settings.shop_budget {
+                for world_context in &mut world_contexts {
+                    apply_shop_budget(budget, world_context)?;
+                }
+            }
+
             let placements = world_contexts.into_iter().map(|world_context| world_context.placements).collect::<Vec<_>>();
             return Ok(placements);
         }
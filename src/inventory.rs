@@ -1,14 +1,39 @@
-use std::fmt;
+use std::{fmt, convert::TryFrom};
 
 use rustc_hash::FxHashMap;
 
-use crate::item::Item;
+use crate::item::{Item, Resource, Skill, Shard, Teleporter};
 
+const RESOURCES: [Resource; 5] = [Resource::Health, Resource::Energy, Resource::Ore, Resource::Keystone, Resource::ShardSlot];
+
+/// A collection of items
+///
+/// The items logic checks all the time have a fixed place so looking them up doesn't need any hashing, everything else is kept in a map
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct Inventory {
-    pub items: FxHashMap<Item, u16>,
+    spirit_light: u16,
+    resources: [u16; 5],
+    skills: u128,  // one bit per skill id
+    ancestral_light: u16,  // the only skill that can be owned more than once
+    shards: u64,  // one bit per shard id
+    teleporters: u32,  // one bit per teleporter id
+    water: bool,
+    other: FxHashMap<Item, u16>,
 }
 impl Inventory {
+    fn set(&mut self, item: Item, amount: u16) {
+        match item {
+            Item::SpiritLight(1) => self.spirit_light = amount,
+            Item::Resource(resource) => self.resources[resource as usize] = amount,
+            Item::Skill(Skill::AncestralLight) => self.ancestral_light = amount,
+            Item::Skill(skill) => if amount > 0 { self.skills |= 1 << skill as u8 } else { self.skills &= !(1 << skill as u8) },
+            Item::Shard(shard) => if amount > 0 { self.shards |= 1 << shard as u8 } else { self.shards &= !(1 << shard as u8) },
+            Item::Teleporter(teleporter) => if amount > 0 { self.teleporters |= 1 << teleporter as u8 } else { self.teleporters &= !(1 << teleporter as u8) },
+            Item::Water => self.water = amount > 0,
+            _ => if amount > 0 { self.other.insert(item, amount); } else { self.other.remove(&item); },
+        }
+    }
+
     pub fn grant(&mut self, mut item: Item, mut amount: u16) {
         let single_instance = item.is_single_instance();
         if single_instance && amount > 1 {
@@ -18,43 +43,61 @@ impl Inventory {
             amount *= stacked_amount;
             item = Item::SpiritLight(1);
         }
-        let prior = self.items.entry(item).or_insert(0);
-        if single_instance {
-            // TODO I feel like in an efficient implementation this shouldn't exist...
-            *prior = amount;
-        } else {
-            *prior += amount;
+        if !single_instance {
+            amount += self.get(&item);
         }
+        self.set(item, amount);
     }
     pub fn remove(&mut self, item: &Item, amount: u16) -> u16 {
-        match self.items.get_mut(item) {
-            Some(prior) => {
-                if amount >= *prior {
-                    let negative = amount - *prior;
-                    self.items.remove(item);
-                    negative
-                } else {
-                    *prior -= amount;
-                    0
-                }
-            },
-            None => amount,
+        let prior = self.get(item);
+        if prior > 0 {
+            self.set(item.clone(), prior.saturating_sub(amount));
         }
+        amount.saturating_sub(prior)
     }
 
+    #[inline]
     pub fn has(&self, item: &Item, amount: u16) -> bool {
-        if let Some(owned) = self.items.get(item) {
-            return *owned >= amount;
-        }
-        false
+        let owned = self.get(item);
+        owned > 0 && owned >= amount
     }
+    #[inline]
     pub fn get(&self, item: &Item) -> u16 {
-        *self.items.get(item).unwrap_or(&0)
+        match item {
+            Item::SpiritLight(1) => self.spirit_light,
+            Item::Resource(resource) => self.resources[*resource as usize],
+            Item::Skill(Skill::AncestralLight) => self.ancestral_light,
+            Item::Skill(skill) => (self.skills >> *skill as u8 & 1) as u16,
+            Item::Shard(shard) => (self.shards >> *shard as u8 & 1) as u16,
+            Item::Teleporter(teleporter) => (self.teleporters >> *teleporter as u8 & 1) as u16,
+            Item::Water => self.water.into(),
+            _ => self.other.get(item).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spirit_light == 0 && self.resources == [0; 5] && self.skills == 0 && self.ancestral_light == 0 && self.shards == 0 && self.teleporters == 0 && !self.water && self.other.is_empty()
+    }
+    /// Every item with the amount owned
+    pub fn iter(&self) -> impl Iterator<Item=(Item, u16)> + '_ {
+        let skills = (0..128).filter(move |id| self.skills >> id & 1 == 1).filter_map(|id| Skill::try_from(id).ok()).map(|skill| (Item::Skill(skill), 1));
+        let shards = (0..64).filter(move |id| self.shards >> id & 1 == 1).filter_map(|id| Shard::try_from(id).ok()).map(|shard| (Item::Shard(shard), 1));
+        let teleporters = (0..32).filter(move |id| self.teleporters >> id & 1 == 1).filter_map(|id| Teleporter::try_from(id).ok()).map(|teleporter| (Item::Teleporter(teleporter), 1));
+
+        std::iter::once((Item::SpiritLight(1), self.spirit_light))
+            .chain(RESOURCES.iter().map(move |&resource| (Item::Resource(resource), self.resources[resource as usize])))
+            .chain(std::iter::once((Item::Skill(Skill::AncestralLight), self.ancestral_light)))
+            .chain(std::iter::once((Item::Water, self.water.into())))
+            .filter(|&(_, amount)| amount > 0)
+            .chain(skills)
+            .chain(shards)
+            .chain(teleporters)
+            .chain(self.other.iter().map(|(item, amount)| (item.clone(), *amount)))
     }
 
     pub fn item_count(&self) -> usize {
         let mut count = 0;
-        for (item, amount) in &self.items {
+        for (item, amount) in self.iter() {
             if let Item::SpiritLight(stacked_amount) = item {
                 count += (amount * stacked_amount + 39) / 40;  // this will usually demand more than necessary, but with the placeholder system that shouldn't be a problem (and underestimating the needed slots can force a retry)
             } else {
@@ -66,7 +109,7 @@ impl Inventory {
     }
     pub fn world_item_count(&self) -> usize {
         let mut count = 0;
-        for (item, amount) in self.items.iter().filter(|&(item, _)| !item.is_multiworld_spread()) {
+        for (item, amount) in self.iter().filter(|(item, _)| !item.is_multiworld_spread()) {
             if let Item::SpiritLight(stacked_amount) = item {
                 count += (amount * stacked_amount + 39) / 40;  // this will usually demand more than necessary, but with the placeholder system that shouldn't be a problem (and underestimating the needed slots can force a retry)
             } else {
@@ -79,26 +122,37 @@ impl Inventory {
 
     pub fn cost(&self) -> f32 {
         let mut cost = 0;
-        for item in self.items.keys() {
-            cost += item.cost() * self.items[item];
+        for (item, amount) in self.iter() {
+            cost += item.cost() * amount;
         }
 
         cost.into()
     }
 
     pub fn contains(&self, other: &Inventory) -> bool {
-        for (item, amount) in &other.items {
-            if !self.has(item, *amount) {
-                return false;
-            }
-        }
-        true
+        self.spirit_light >= other.spirit_light &&
+        self.resources.iter().zip(&other.resources).all(|(owned, amount)| owned >= amount) &&
+        self.skills & other.skills == other.skills &&
+        self.ancestral_light >= other.ancestral_light &&
+        self.shards & other.shards == other.shards &&
+        self.teleporters & other.teleporters == other.teleporters &&
+        (self.water || !other.water) &&
+        other.other.iter().all(|(item, amount)| self.has(item, *amount))
     }
 
     pub fn merge(&self, other: &Inventory) -> Inventory {
         let mut merged = self.clone();
-        for (item, amount) in other.items.clone() {
-            merged.grant(item, amount);
+        merged.spirit_light += other.spirit_light;
+        for (merged, amount) in merged.resources.iter_mut().zip(&other.resources) {
+            *merged += amount;
+        }
+        merged.skills |= other.skills;
+        merged.ancestral_light += other.ancestral_light;
+        merged.shards |= other.shards;
+        merged.teleporters |= other.teleporters;
+        merged.water |= other.water;
+        for (item, amount) in &other.other {
+            merged.grant(item.clone(), *amount);
         }
         merged
     }
@@ -106,8 +160,8 @@ impl Inventory {
 
 impl fmt::Display for Inventory {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let display = self.items.iter().map(|(item, amount)| {
-            if amount == &1 {
+        let display = self.iter().map(|(item, amount)| {
+            if amount == 1 {
                 format!("{}", item)
             } else {
                 format!("{} {}", amount, item)
@@ -879,7 +879,7 @@ pub fn parse_item(item: &str) -> Result<Item, String> {
     parse_item_parts(parts).map_err(|err| format!("{} in item {}", err, item))
 }
 
-fn parse_count(item: &mut &str) -> u16 {
+pub fn parse_count(item: &mut &str) -> u16 {
     if let Some(index) = item.find('x') {
         let amount = item[..index].trim();
         if let Ok(amount) = amount.parse::<u16>() {
@@ -1482,7 +1482,7 @@ pub fn validate_header(name: &Path, contents: &str) -> Result<(Vec<UberState>, H
     Ok((occupied_states, context.excludes))
 }
 
-fn where_is(pattern: &str, world_index: usize, seeds: &[String], graph: &Graph, settings: &Settings) -> Result<String, String> {
+fn where_is(pattern: &str, world_index: usize, seeds: &[String], graphs: &[&Graph], settings: &Settings) -> Result<String, String> {
     let re = Regex::new(&format!(r"^({})$", pattern)).map_err(|err| format!("Invalid regex {}: {}", pattern, err))?;
 
     for mut line in seeds[world_index].lines() {
@@ -1508,7 +1508,7 @@ fn where_is(pattern: &str, world_index: usize, seeds: &[String], graph: &Graph,
                 other_worlds.remove(world_index);
 
                 for other_world_index in other_worlds {
-                    let actual_zone = where_is(&actual_item, other_world_index, seeds, graph, settings)?;
+                    let actual_zone = where_is(&actual_item, other_world_index, seeds, graphs, settings)?;
                     if &actual_zone != "Unknown" {
                         let player_name = settings.players.get(other_world_index).cloned().unwrap_or_else(|| format!("Player {}", other_world_index + 1));
 
@@ -1519,7 +1519,7 @@ fn where_is(pattern: &str, world_index: usize, seeds: &[String], graph: &Graph,
                 return Ok(String::from("Spawn"));
             } else {
                 let uber_state = UberState::from_parts(uber_group, uber_id)?;
-                if let Some(node) = graph.nodes.iter().find(|&node| node.uber_state() == Some(&uber_state)) {
+                if let Some(node) = graphs[world_index].nodes.iter().find(|&node| node.uber_state() == Some(&uber_state)) {
                     if let Some(zone) = node.zone() {
                         return Ok(zone.to_string());
                     }
@@ -1588,7 +1588,7 @@ fn how_many(pattern: &str, zone: Zone, world_index: usize, seeds: &[String], gra
     Ok(locations)
 }
 
-pub fn postprocess(seeds: &mut Vec<String>, graph: &Graph, settings: &Settings) -> Result<(), String> {
+pub fn postprocess(seeds: &mut Vec<String>, graphs: &[&Graph], settings: &Settings) -> Result<(), String> {
     let clone = seeds.clone();
 
     for (world_index, seed) in seeds.iter_mut().enumerate() {
@@ -1603,7 +1603,7 @@ pub fn postprocess(seeds: &mut Vec<String>, graph: &Graph, settings: &Settings)
                 if let Some(end_index) = read_args(seed, after_bracket) {
                     let pattern = seed[after_bracket..end_index].trim();
 
-                    let zone = where_is(pattern, world_index, &clone, graph, settings)?;
+                    let zone = where_is(pattern, world_index, &clone, graphs, settings)?;
                     seed.replace_range(start_index..=end_index, &zone);
 
                     continue;
@@ -1627,7 +1627,7 @@ pub fn postprocess(seeds: &mut Vec<String>, graph: &Graph, settings: &Settings)
                     let zone = Zone::try_from(zone).map_err(|_| format!("invalid zone {}", zone))?;
                     let pattern = args.next().unwrap_or("").trim();
 
-                    let locations = how_many(pattern, zone, world_index, &clone, graph)?;
+                    let locations = how_many(pattern, zone, world_index, &clone, graphs[world_index])?;
                     let locations = locations.into_iter().map(|uber_state| uber_state.to_string()).collect::<Vec<_>>();
                     let locations = locations.join(",").replace('|', ",");
 
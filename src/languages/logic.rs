@@ -1,24 +1,21 @@
 pub mod tokenizer;
 pub mod parser;
 pub mod emitter;
+pub mod lint;
+pub mod diff;
 
 use std::{path::Path, convert::TryFrom};
 
 use decorum::R32;
-use parser::ParseError;
+use parser::{AreaTree, ParseError};
+use tokenizer::Metadata;
 use serde::Deserialize;
 use crate::world::graph::Graph;
 use crate::settings::Settings;
 use crate::util::{self, UberState, Position};
 
-pub fn parse_logic<P1, P2, P3>(areas: P1, locations: P2, states: P3, settings: &Settings, validate: bool) -> Result<Graph, String>
-where
-    P1: AsRef<Path>,
-    P2: AsRef<Path>,
-    P3: AsRef<Path>,
-{
-    let input = util::read_file(&areas, "logic")?;
-    let (tokens, metadata) = tokenizer::tokenize(&input).map_err(|err| format!("Error parsing areas from {}: {}", areas.as_ref().display(), err))?;
+fn parse_area_tree<'a>(input: &'a str, path: &Path) -> Result<(AreaTree<'a>, Metadata<'a>), String> {
+    let (tokens, metadata) = tokenizer::tokenize(input).map_err(|err| format!("Error parsing areas from {}: {}", path.display(), err))?;
 
     let areas = parser::parse_areas(tokens, &metadata).map_err(|err| {
         let ParseError { description, position } = err;
@@ -30,6 +27,18 @@ where
         format!("Error parsing areas.wotw: {}: {}", description, line)
     })?;
 
+    Ok((areas, metadata))
+}
+
+pub fn parse_logic<P1, P2, P3>(areas: P1, locations: P2, states: P3, settings: &Settings, validate: bool) -> Result<Graph, String>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    P3: AsRef<Path>,
+{
+    let input = util::read_file(&areas, "logic")?;
+    let (areas, metadata) = parse_area_tree(&input, areas.as_ref())?;
+
     let input = util::read_file(&locations, "logic")?;
     let locations = parse_locations(&input).map_err(|err| format!("Error parsing locations from {}: {}", locations.as_ref().display(), err))?;
 
@@ -39,6 +48,24 @@ where
     emitter::emit(&areas, &metadata, &locations, &state_map, settings, validate).map_err(|err| format!("Error building the logic: {}", err))
 }
 
+pub fn lint_logic<P1, P2, P3>(areas: P1, locations: P2, states: P3) -> Result<Vec<lint::LintIssue>, String>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    P3: AsRef<Path>,
+{
+    let input = util::read_file(&areas, "logic")?;
+    let (areas, metadata) = parse_area_tree(&input, areas.as_ref())?;
+
+    let input = util::read_file(&locations, "logic")?;
+    let locations = parse_locations(&input).map_err(|err| format!("Error parsing locations from {}: {}", locations.as_ref().display(), err))?;
+
+    let input = util::read_file(&states, "logic")?;
+    let state_map = parse_states(&input).map_err(|err| format!("Error parsing states from {}: {}", states.as_ref().display(), err))?;
+
+    lint::lint(&areas, &metadata, &locations, &state_map).map_err(|err| format!("Error building the logic: {}", err))
+}
+
 #[derive(Debug)]
 pub struct Location {
     pub name: String,
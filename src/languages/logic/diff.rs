@@ -0,0 +1,306 @@
+use std::{collections::VecDeque, fmt, hash::Hash, path::Path};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::{emitter, parse_area_tree, parse_locations, parse_states, Location, NamedState};
+use crate::inventory::Inventory;
+use crate::world::{graph::{Graph, Node}, player::Player};
+use crate::settings::Settings;
+use crate::util::{self, constants::DEFAULT_SPAWN};
+
+#[derive(Debug, PartialEq)]
+pub enum LogicChange {
+    /// The minimal itemsets needed to collect a pickup from the spawn changed
+    Pickup { difficulty: String, identifier: String, old: Vec<String>, new: Vec<String> },
+    AddedDifficulty { name: String },
+    RemovedDifficulty { name: String },
+    AddedConnection { from: String, to: String },
+    RemovedConnection { from: String, to: String },
+    /// The requirement of a connection which exists in both versions changed
+    Requirement { difficulty: String, from: String, to: String, old: String, new: String },
+    Refills { difficulty: String, anchor: String, old: Vec<String>, new: Vec<String> },
+}
+impl fmt::Display for LogicChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let itemsets = |itemsets: &[String]| if itemsets.is_empty() { String::from("impossible") } else { itemsets.join(" | ") };
+        match self {
+            LogicChange::Pickup { difficulty, identifier, old, new } => write!(f, "[{}] {}: {} -> {}", difficulty, identifier, itemsets(old), itemsets(new)),
+            LogicChange::AddedDifficulty { name } => write!(f, "+ difficulty {}", name),
+            LogicChange::RemovedDifficulty { name } => write!(f, "- difficulty {}", name),
+            LogicChange::AddedConnection { from, to } => write!(f, "+ {} -> {}", from, to),
+            LogicChange::RemovedConnection { from, to } => write!(f, "- {} -> {}", from, to),
+            LogicChange::Requirement { difficulty, from, to, old, new } => write!(f, "[{}] {} -> {}: {} => {}", difficulty, from, to, old, new),
+            LogicChange::Refills { difficulty, anchor, old, new } => write!(f, "[{}] {} refills: {} -> {}", difficulty, anchor, old.join(" | "), new.join(" | ")),
+        }
+    }
+}
+
+// connections between the same nodes are alternatives to each other, so they get joined into one requirement
+fn connection_requirements(graph: &Graph) -> FxHashMap<(String, String), String> {
+    let mut requirements = FxHashMap::<(String, String), Vec<String>>::default();
+    for node in &graph.nodes {
+        if let Node::Anchor(anchor) = node {
+            for connection in &anchor.connections {
+                let key = (anchor.identifier.clone(), graph.nodes[connection.to].identifier().to_string());
+                requirements.entry(key).or_default().push(graph.display_requirement(&connection.requirement));
+            }
+        }
+    }
+
+    requirements.into_iter().map(|(key, requirements)| (key, requirements.join(" OR "))).collect()
+}
+
+fn display_itemset(inventory: &Inventory) -> String {
+    if inventory.is_empty() { String::from("free") } else { inventory.to_string() }
+}
+
+// what is needed for two connections in a row, a reach check asks the same inventory for both
+fn union(left: &Inventory, right: &Inventory) -> Inventory {
+    let mut union = left.clone();
+    for (item, amount) in right.iter() {
+        let owned = left.get(&item);
+        if amount > owned { union.grant(item, amount - owned); }
+    }
+    union
+}
+
+// past this many alternatives only the smallest itemsets are kept, otherwise they multiply along the paths through the graph
+const ITEMSET_LIMIT: usize = 8;
+
+// keeps only the smallest itemsets which aren't a superset of another one, returns whether the itemset was added
+fn add_minimal(itemsets: &mut Vec<Inventory>, itemset: Inventory) -> bool {
+    if itemsets.iter().any(|other| itemset.contains(other)) { return false; }
+    let count = itemset.item_count();
+    if itemsets.len() >= ITEMSET_LIMIT && itemsets[ITEMSET_LIMIT - 1].item_count() <= count { return false; }
+
+    itemsets.retain(|other| !other.contains(&itemset));
+    let position = itemsets.iter().position(|other| other.item_count() > count).unwrap_or(itemsets.len());
+    itemsets.insert(position, itemset);
+    itemsets.truncate(ITEMSET_LIMIT);
+    true
+}
+
+// states are assumed to be met and orb costs are left out, this compares what has to be found rather than how it is used
+fn pickup_itemsets(graph: &Graph) -> Result<FxHashMap<String, Vec<String>>, String> {
+    let player = Player { difficulty: graph.difficulty, ..Player::default() };
+    let states = graph.nodes.iter().filter(|node| matches!(node, Node::State(_))).map(Node::index).collect::<Vec<_>>();
+    let spawn = graph.find_spawn(DEFAULT_SPAWN)?.index();
+
+    let needed = graph.nodes.iter().map(|node| match node {
+        Node::Anchor(anchor) => anchor.connections.iter().map(|connection| {
+            let mut needed = connection.requirement.items_needed(&player, &states).into_iter().map(|(inventory, _)| inventory).collect::<Vec<_>>();
+            needed.sort_by_cached_key(Inventory::item_count);
+            let mut minimal = Vec::new();
+            for inventory in needed {
+                if minimal.len() == ITEMSET_LIMIT { break; }
+                add_minimal(&mut minimal, inventory);
+            }
+            minimal
+        }).collect(),
+        _ => Vec::new(),
+    }).collect::<Vec<Vec<_>>>();
+
+    let mut inventories = FxHashMap::<usize, Vec<Inventory>>::default();
+    inventories.insert(spawn, vec![Inventory::default()]);
+    let mut queue = VecDeque::from(vec![spawn]);
+    let mut queued = FxHashSet::default();
+    queued.insert(spawn);
+    while let Some(index) = queue.pop_front() {
+        queued.remove(&index);
+        let anchor = if let Node::Anchor(anchor) = &graph.nodes[index] { anchor } else { continue };
+        let from = inventories[&index].clone();
+
+        for (connection, needed) in anchor.connections.iter().zip(&needed[index]) {
+            let target = inventories.entry(connection.to).or_default();
+            let mut improved = false;
+            for inventory in &from {
+                for needed in needed {
+                    improved |= add_minimal(target, union(inventory, needed));
+                }
+            }
+            if improved && queued.insert(connection.to) { queue.push_back(connection.to); }
+        }
+    }
+
+    Ok(graph.nodes.iter().filter(|node| node.can_place()).map(|node| {
+        let mut itemsets = inventories.get(&node.index()).map_or_else(Vec::new, |itemsets| itemsets.iter().map(display_itemset).collect());
+        itemsets.sort();
+        (node.identifier().to_string(), itemsets)
+    }).collect())
+}
+
+fn anchor_refills(graph: &Graph) -> FxHashMap<String, Vec<String>> {
+    graph.nodes.iter().filter_map(|node| match node {
+        Node::Anchor(anchor) => {
+            let refills = anchor.refills.iter().map(|refill| format!("{:?}: {}", refill.name, graph.display_requirement(&refill.requirement))).collect();
+            Some((anchor.identifier.clone(), refills))
+        },
+        _ => None,
+    }).collect()
+}
+
+fn connections(graphs: &[(String, Graph)]) -> FxHashSet<(String, String)> {
+    graphs.iter().flat_map(|(_, graph)| connection_requirements(graph).into_keys()).collect()
+}
+
+// the keys present in both maps whose values differ, in order
+fn diff_map<'a, K, V>(old: &'a FxHashMap<K, V>, new: &'a FxHashMap<K, V>) -> Vec<(&'a K, &'a V, &'a V)>
+where
+    K: Ord + Hash,
+    V: PartialEq,
+{
+    let mut changes = old.iter().filter_map(|(key, old)| {
+        let new = new.get(key)?;
+        if old == new { None } else { Some((key, old, new)) }
+    }).collect::<Vec<_>>();
+    changes.sort_by_key(|&(key, _, _)| key);
+    changes
+}
+
+/// Compare two versions of the logic on every difficulty either of them declares
+///
+/// Each version is given as the graphs it emits on each of its difficulties
+pub fn diff_graphs(old: &[(String, Graph)], new: &[(String, Graph)]) -> Result<Vec<LogicChange>, String> {
+    let mut changes = Vec::new();
+
+    let (old_connections, new_connections) = (connections(old), connections(new));
+    let mut removed = old_connections.difference(&new_connections).cloned().collect::<Vec<_>>();
+    removed.sort();
+    changes.extend(removed.into_iter().map(|(from, to)| LogicChange::RemovedConnection { from, to }));
+    let mut added = new_connections.difference(&old_connections).cloned().collect::<Vec<_>>();
+    added.sort();
+    changes.extend(added.into_iter().map(|(from, to)| LogicChange::AddedConnection { from, to }));
+
+    for (name, old) in old {
+        let new = match new.iter().find(|(other, _)| other == name) {
+            Some((_, new)) => new,
+            None => {
+                changes.push(LogicChange::RemovedDifficulty { name: name.clone() });
+                continue;
+            },
+        };
+
+        let (old_requirements, new_requirements) = (connection_requirements(old), connection_requirements(new));
+        // searching the itemsets is the expensive part, and they can't differ without a different connection
+        if old_requirements != new_requirements {
+            let (old_itemsets, new_itemsets) = (pickup_itemsets(old)?, pickup_itemsets(new)?);
+            changes.extend(diff_map(&old_itemsets, &new_itemsets).into_iter().map(|(identifier, old, new)| LogicChange::Pickup {
+                difficulty: name.clone(),
+                identifier: identifier.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            }));
+        }
+        changes.extend(diff_map(&old_requirements, &new_requirements).into_iter().map(|((from, to), old, new)| LogicChange::Requirement {
+            difficulty: name.clone(),
+            from: from.clone(),
+            to: to.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }));
+        let (old_refills, new_refills) = (anchor_refills(old), anchor_refills(new));
+        changes.extend(diff_map(&old_refills, &new_refills).into_iter().map(|(anchor, old, new)| LogicChange::Refills {
+            difficulty: name.clone(),
+            anchor: anchor.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }));
+    }
+    for (name, _) in new {
+        if !old.iter().any(|(other, _)| other == name) {
+            changes.push(LogicChange::AddedDifficulty { name: name.clone() });
+        }
+    }
+
+    Ok(changes)
+}
+
+fn emit_difficulties(areas: &Path, locations: &[Location], state_map: &[NamedState]) -> Result<Vec<(String, Graph)>, String> {
+    let input = util::read_file(areas, "logic")?;
+    let (areas, metadata) = parse_area_tree(&input, areas)?;
+
+    metadata.difficulties.iter().map(|&difficulty| {
+        let settings = Settings { difficulty: difficulty.to_string(), ..Settings::default() };
+        let graph = emitter::emit(&areas, &metadata, locations, state_map, &settings, false).map_err(|err| format!("Error building the logic: {}", err))?;
+        Ok((difficulty.to_string(), graph))
+    }).collect()
+}
+
+/// Parse two versions of the areas file at every difficulty they declare and compare them
+pub fn diff_logic<P1, P2, P3, P4>(old_areas: P1, new_areas: P2, locations: P3, states: P4) -> Result<Vec<LogicChange>, String>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    P3: AsRef<Path>,
+    P4: AsRef<Path>,
+{
+    let input = util::read_file(&locations, "logic")?;
+    let locations = parse_locations(&input).map_err(|err| format!("Error parsing locations from {}: {}", locations.as_ref().display(), err))?;
+
+    let input = util::read_file(&states, "logic")?;
+    let state_map = parse_states(&input).map_err(|err| format!("Error parsing states from {}: {}", states.as_ref().display(), err))?;
+
+    let old = emit_difficulties(old_areas.as_ref(), &locations, &state_map)?;
+    let new = emit_difficulties(new_areas.as_ref(), &locations, &state_map)?;
+
+    diff_graphs(&old, &new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::Skill;
+    use crate::world::requirements::Requirement;
+
+    #[test]
+    fn diff() {
+        let locations = parse_locations(&util::read_file("loc_data.csv", "logic").unwrap()).unwrap();
+        let state_map = parse_states(&util::read_file("state_data.csv", "logic").unwrap()).unwrap();
+        let old = emit_difficulties(Path::new("areas.wotw"), &locations, &state_map).unwrap();
+        assert!(diff_graphs(&old, &old).unwrap().is_empty());
+
+        let mut new = old.clone();
+        for (_, graph) in &mut new {
+            let index = graph.find_spawn("MarshSpawn.Main").unwrap().index();
+            if let Node::Anchor(anchor) = &mut graph.nodes[index] {
+                let removed = anchor.connections[0].to;
+                anchor.connections.retain(|connection| connection.to != removed);
+                for connection in &mut anchor.connections {
+                    if let Requirement::Free = connection.requirement {
+                        connection.requirement = Requirement::Skill(Skill::Bash);
+                    }
+                }
+            }
+        }
+        // a change further away from the spawn, only on one difficulty
+        let (_, graph) = new.iter_mut().find(|(name, _)| name == "gorlek").unwrap();
+        let index = graph.nodes.iter().position(|node| node.identifier() == "MarshSpawn.CaveEntrance").unwrap();
+        if let Node::Anchor(anchor) = &mut graph.nodes[index] {
+            anchor.connections[0].requirement = Requirement::Impossible;
+        }
+        let (_, unsafe_graph) = new.last().unwrap();
+        new.push((String::from("unsafeplus"), unsafe_graph.clone()));
+
+        let changes = diff_graphs(&old, &new).unwrap();
+        assert!(changes.iter().any(|change| matches!(change, LogicChange::RemovedConnection { from, .. } if from == "MarshSpawn.Main")));
+        assert!(changes.contains(&LogicChange::Requirement {
+            difficulty: String::from("moki"),
+            from: String::from("MarshSpawn.Main"),
+            to: String::from("MarshSpawn.FirstPickupEX"),
+            old: String::from("free"),
+            new: String::from("Bash"),
+        }));
+        let cave_entrance = changes.iter().filter_map(|change| match change {
+            LogicChange::Requirement { difficulty, from, new, .. } if from == "MarshSpawn.CaveEntrance" => Some((difficulty.as_str(), new.as_str())),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(cave_entrance, vec![("gorlek", "Impossible")]);
+        assert!(changes.contains(&LogicChange::Pickup {
+            difficulty: String::from("moki"),
+            identifier: String::from("MarshSpawn.FirstPickupEX"),
+            old: vec![String::from("free")],
+            new: vec![String::from("Bash")],
+        }));
+        assert!(changes.contains(&LogicChange::AddedDifficulty { name: String::from("unsafeplus") }));
+    }
+}
@@ -13,7 +13,10 @@ use crate::util::{
 
 struct EmitterContext<'a> {
     definitions: &'a FxHashMap<&'a str, parser::Group<'a>>,
+    metadata: &'a Metadata<'a>,
     settings: &'a Settings,
+    difficulty: usize,
+    base_difficulty: Difficulty,
     validate: bool,
     node_map: FxHashMap<&'a str, usize>,
     used_states: FxHashSet<&'a str>,
@@ -31,18 +34,20 @@ fn build_requirement<'a>(requirement: &parser::Requirement<'a>, region: bool, co
     match requirement {
         parser::Requirement::Free => Requirement::Free,
         parser::Requirement::Definition(identifier) => build_requirement_group(&context.definitions[identifier], region, context),
-        parser::Requirement::Difficulty(difficulty) =>
+        parser::Requirement::Difficulty(difficulty) => {
+            let difficulty = context.metadata.difficulty_rank(difficulty).unwrap();
             if region {
-                if context.settings.difficulty == *difficulty {
+                if context.difficulty == difficulty {
                     Requirement::Free
                 } else {
                     Requirement::Impossible
                 }
-            } else if context.settings.difficulty >= *difficulty {
+            } else if context.difficulty >= difficulty {
                 Requirement::Free
             } else {
                 Requirement::Impossible
-            },
+            }
+        },
         parser::Requirement::Glitch(glitch) => build_glitch_requirement(glitch, Requirement::Free, context),
         parser::Requirement::Skill(skill) => Requirement::Skill(*skill),
         parser::Requirement::EnergySkill(skill, amount) => Requirement::EnergySkill(*skill, (*amount).into()),
@@ -66,11 +71,11 @@ fn build_requirement<'a>(requirement: &parser::Requirement<'a>, region: bool, co
                 Requirement::Skill(Skill::Hammer),
                 Requirement::EnergySkill(Skill::Bow, 1.0),
             ];
-            if context.settings.difficulty >= Difficulty::Gorlek {
+            if context.base_difficulty >= Difficulty::Gorlek {
                 allowed_weapons.push(Requirement::EnergySkill(Skill::Shuriken, 1.0));
                 allowed_weapons.push(Requirement::EnergySkill(Skill::Grenade, 1.0));
             }
-            if context.settings.difficulty >= Difficulty::Unsafe {
+            if context.base_difficulty >= Difficulty::Unsafe {
                 allowed_weapons.push(Requirement::EnergySkill(Skill::Spear, 1.0));
             }
             Requirement::Or(allowed_weapons)
@@ -180,6 +185,9 @@ fn add_entry<'a>(graph: &mut FxHashMap<&'a str, usize>, key: &'a str, index: usi
 }
 
 pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state_map: &[NamedState], settings: &Settings, validate: bool) -> Result<Graph, String> {
+    let difficulty = metadata.difficulties.iter().position(|name| name.eq_ignore_ascii_case(&settings.difficulty)).ok_or_else(|| format!("Unknown difficulty {}", settings.difficulty))?;
+    let base_difficulty = metadata.base_difficulty(metadata.difficulties[difficulty]).unwrap();
+
     let node_count = areas.anchors.len() + locations.len() + metadata.states.len();
     let mut graph = Vec::with_capacity(node_count);
     let mut used_states = FxHashSet::default();
@@ -253,7 +261,10 @@ pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state
 
     let mut context = EmitterContext {
         definitions: &areas.definitions,
+        metadata,
         settings,
+        difficulty,
+        base_difficulty,
         validate,
         node_map,
         used_states,
@@ -290,6 +301,7 @@ pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state
             connections.push(graph::Connection {
                 to,
                 requirement,
+                door: connection.door,
             });
         }
 
@@ -327,5 +339,9 @@ pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state
 
     Ok(Graph {
         nodes: graph,
+        difficulty: base_difficulty,
+        redirects: Vec::new(),
+        door_redirects: Vec::new(),
+        keystone_doors: Vec::new(),
     })
 }
@@ -0,0 +1,239 @@
+use std::{fmt, convert::TryFrom};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+
+use super::{emitter, parser::{self, AreaTree}, tokenizer::Metadata, Location, NamedState};
+use crate::world::{World, graph::Node, pool::Pool, requirements::Requirement};
+use crate::item::{Item, Teleporter};
+use crate::settings::Settings;
+use crate::util::{Glitch, NodeType, auto_display, constants::DEFAULT_SPAWN};
+
+const ALL_GLITCHES: &[Glitch] = &[
+    Glitch::SwordSentryJump, Glitch::HammerSentryJump, Glitch::ShurikenBreak, Glitch::SentryBreak, Glitch::HammerBreak, Glitch::SpearBreak,
+    Glitch::SentryBurn, Glitch::RemoveKillPlane, Glitch::LaunchSwap, Glitch::SentrySwap, Glitch::FlashSwap, Glitch::BlazeSwap, Glitch::WaveDash,
+    Glitch::GrenadeJump, Glitch::HammerJump, Glitch::SwordJump, Glitch::GrenadeRedirect, Glitch::SentryRedirect, Glitch::PauseHover,
+    Glitch::GlideJump, Glitch::GlideHammerJump, Glitch::SpearJump,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LintKind {
+    UnreachableAnchor,
+    MissingLocation,
+    UnknownLocation,
+    UnusedState,
+    UnusedDefinition,
+    DuplicateConnection,
+    ImpossibleRequirement,
+}
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", auto_display(self).to_lowercase())
+    }
+}
+
+/// A problem found in the logic files, identified by the node or connection it concerns
+#[derive(Debug, Serialize)]
+pub struct LintIssue {
+    pub kind: LintKind,
+    pub subject: String,
+    pub message: String,
+}
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.kind, self.subject, self.message)
+    }
+}
+
+fn use_definitions<'a>(group: &parser::Group<'a>, definitions: &FxHashMap<&'a str, parser::Group<'a>>, used: &mut FxHashSet<&'a str>) {
+    for line in &group.lines {
+        for requirement in line.ands.iter().chain(&line.ors) {
+            if let parser::Requirement::Definition(identifier) = requirement {
+                if used.insert(identifier) {
+                    if let Some(definition) = definitions.get(identifier) {
+                        use_definitions(definition, definitions, used);
+                    }
+                }
+            }
+        }
+        if let Some(subgroup) = &line.group {
+            use_definitions(subgroup, definitions, used);
+        }
+    }
+}
+
+fn lint_structure(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state_map: &[NamedState], issues: &mut Vec<LintIssue>) {
+    let location_names = locations.iter().map(|location| &location.name[..]).collect::<FxHashSet<_>>();
+    let anchor_names = areas.anchors.iter().map(|anchor| anchor.identifier).collect::<FxHashSet<_>>();
+
+    let mut connected_locations = FxHashSet::default();
+    let mut used_definitions = FxHashSet::default();
+    for region in areas.regions.values() {
+        use_definitions(region, &areas.definitions, &mut used_definitions);
+    }
+
+    for anchor in &areas.anchors {
+        for refill in &anchor.refills {
+            if let Some(group) = &refill.requirements {
+                use_definitions(group, &areas.definitions, &mut used_definitions);
+            }
+        }
+
+        let mut targets = FxHashSet::default();
+        for connection in &anchor.connections {
+            use_definitions(&connection.requirements, &areas.definitions, &mut used_definitions);
+
+            if !targets.insert(connection.identifier) {
+                issues.push(LintIssue {
+                    kind: LintKind::DuplicateConnection,
+                    subject: format!("{} -> {}", anchor.identifier, connection.identifier),
+                    message: format!("Connects to this {:?} more than once", connection.name),
+                });
+            }
+
+            let exists = match connection.name {
+                NodeType::Pickup | NodeType::Quest => location_names.contains(connection.identifier),
+                NodeType::State => metadata.states.contains(connection.identifier),
+                NodeType::Anchor => anchor_names.contains(connection.identifier),
+            };
+            if matches!(connection.name, NodeType::Pickup | NodeType::Quest) {
+                connected_locations.insert(connection.identifier);
+            }
+            if !exists {
+                issues.push(LintIssue {
+                    kind: LintKind::UnknownLocation,
+                    subject: connection.identifier.to_string(),
+                    message: format!("{} connects to this {:?}, but it doesn't exist", anchor.identifier, connection.name),
+                });
+            }
+        }
+    }
+
+    for location in locations {
+        if !connected_locations.contains(&location.name[..]) {
+            issues.push(LintIssue {
+                kind: LintKind::MissingLocation,
+                subject: location.name.clone(),
+                message: String::from("In the location data, but no anchor connects to it"),
+            });
+        }
+    }
+    for state in state_map {
+        if !metadata.states.contains(&state.name[..]) {
+            issues.push(LintIssue {
+                kind: LintKind::UnusedState,
+                subject: state.name.clone(),
+                message: String::from("In the state data, but never referenced in the logic"),
+            });
+        }
+    }
+
+    let mut unused_definitions = areas.definitions.keys().filter(|&definition| !used_definitions.contains(definition)).collect::<Vec<_>>();
+    unused_definitions.sort_unstable();
+    for definition in unused_definitions {
+        issues.push(LintIssue {
+            kind: LintKind::UnusedDefinition,
+            subject: definition.to_string(),
+            message: String::from("This requirement definition is never used"),
+        });
+    }
+}
+
+/// Check the logic for mistakes that don't prevent it from being used, but are unlikely to be intended
+///
+/// Reachability and impossible requirements are checked on the hardest difficulty with every glitch, so anything reported there can't be used on any settings
+pub fn lint(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state_map: &[NamedState]) -> Result<Vec<LintIssue>, String> {
+    let mut issues = Vec::new();
+    lint_structure(areas, metadata, locations, state_map, &mut issues);
+    if issues.iter().any(|issue| issue.kind == LintKind::UnknownLocation) {
+        return Ok(issues);  // the logic can't be built like this
+    }
+
+    let hardest = metadata.difficulties.last().copied().unwrap_or("unsafe");
+    let settings = Settings {
+        difficulty: hardest.to_string(),
+        glitches: ALL_GLITCHES.to_vec(),
+        ..Settings::default()
+    };
+    let graph = emitter::emit(areas, metadata, locations, state_map, &settings, false)?;
+
+    for node in &graph.nodes {
+        if let Node::Anchor(anchor) = node {
+            for connection in &anchor.connections {
+                if let Requirement::Impossible = connection.requirement {
+                    issues.push(LintIssue {
+                        kind: LintKind::ImpossibleRequirement,
+                        subject: format!("{} -> {}", anchor.identifier, graph.nodes[connection.to].identifier()),
+                        message: String::from("The requirements for this connection can never be met"),
+                    });
+                }
+            }
+            for refill in &anchor.refills {
+                if let Requirement::Impossible = refill.requirement {
+                    issues.push(LintIssue {
+                        kind: LintKind::ImpossibleRequirement,
+                        subject: format!("{} {:?}", anchor.identifier, refill.name),
+                        message: String::from("The requirements for this refill can never be met"),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut world = World::new(&graph);
+    world.player.apply_settings(&settings);
+    world.player.inventory = Pool::preset().inventory;
+    world.player.inventory.grant(Item::SpiritLight(1), 10000);
+    for id in 0..u8::MAX {
+        if let Ok(teleporter) = Teleporter::try_from(id) {
+            world.player.inventory.grant(Item::Teleporter(teleporter), 1);
+        }
+    }
+
+    let spawn = graph.find_spawn(DEFAULT_SPAWN)?;
+    let reached = graph.reached_anchors(&world.player, spawn, &world.uber_states, &world.sets).into_iter().map(Node::index).collect::<FxHashSet<_>>();
+    for node in &graph.nodes {
+        if let Node::Anchor(anchor) = node {
+            if !reached.contains(&anchor.index) {
+                issues.push(LintIssue {
+                    kind: LintKind::UnreachableAnchor,
+                    subject: anchor.identifier.clone(),
+                    message: format!("Can't be reached from {} with every item on {} with all glitches", DEFAULT_SPAWN, hardest),
+                });
+            }
+        }
+    }
+
+    Ok(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages::logic::{tokenizer, parser, parse_locations, parse_states};
+
+    #[test]
+    fn lint() {
+        let areas = "requirement Unused:\n  moki: Bash\n\nregion Lonely:\n  moki: free\n\nanchor MarshSpawn.Main at -799, -4310:\n  state MarshSpawn.LogBroken: free\n  pickup MarshSpawn.RockHC: free\n  pickup MarshSpawn.RockHC:\n    moki: Bash\n\nanchor Lonely.Island at 0, 0:\n  conn MarshSpawn.Main: free\n";
+        let locations = "MarshSpawn.RockHC, Inkwater Marsh, Resource, Life, swampStateGroup, 21786, healthContainerA, 60210, -958, -4313\nMarshSpawn.FirstPickupEX, Inkwater Marsh, SpiritLight, 50, swampStateGroup, 21786, smallExpA, 49485, -764, -4313\n";
+        let states = "MarshSpawn.LogBroken, 21786, 1\nMarshSpawn.HowlBurnt, 21786, 25095\n";
+
+        let (tokens, metadata) = tokenizer::tokenize(areas).unwrap();
+        let areas = parser::parse_areas(tokens, &metadata).unwrap();
+        let locations = parse_locations(locations).unwrap();
+        let states = parse_states(states).unwrap();
+
+        let issues = super::lint(&areas, &metadata, &locations, &states).unwrap();
+        let mut issues = issues.iter().map(|issue| (issue.kind, &issue.subject[..])).collect::<Vec<_>>();
+        issues.sort_unstable_by_key(|&(kind, _)| kind as u8);
+        assert_eq!(issues, vec![
+            (LintKind::UnreachableAnchor, "Lonely.Island"),
+            (LintKind::MissingLocation, "MarshSpawn.FirstPickupEX"),
+            (LintKind::UnusedState, "MarshSpawn.HowlBurnt"),
+            (LintKind::UnusedDefinition, "Unused"),
+            (LintKind::DuplicateConnection, "MarshSpawn.Main -> MarshSpawn.RockHC"),
+            (LintKind::ImpossibleRequirement, "Lonely.Island -> MarshSpawn.Main"),
+        ]);
+    }
+}
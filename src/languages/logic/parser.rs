@@ -4,7 +4,7 @@ use smallvec::SmallVec;
 
 use super::tokenizer::{Token, TokenType, Metadata};
 use crate::item::{Resource, Skill, Shard, Teleporter};
-use crate::util::{Difficulty, Glitch, RefillType, NodeType, Enemy, Position};
+use crate::util::{Glitch, RefillType, NodeType, Enemy, Position};
 
 #[derive(Debug)]
 pub struct ParseError {
@@ -24,7 +24,7 @@ impl ParseError {
 pub enum Requirement<'a> {
     Free,
     Definition(&'a str),
-    Difficulty(Difficulty),
+    Difficulty(&'a str),
     Glitch(Glitch),
     Skill(Skill),
     EnergySkill(Skill, u16),
@@ -83,6 +83,7 @@ pub struct Connection<'a> {
     pub name: NodeType,
     pub identifier: &'a str,
     pub requirements: Group<'a>,
+    pub door: Option<i32>,  // the randomizer's id for the door this connection goes through
 }
 #[derive(Debug)]
 pub struct Anchor<'a> {
@@ -279,7 +280,6 @@ fn parse_requirement<'a>(token: &Token<'a>, metadata: &Metadata) -> Result<Requi
             "Glide" => Ok(Requirement::Skill(Skill::Glide)),
             "GlideHammerJump" => Ok(Requirement::GlideHammerJump),
             "GlideJump" => Ok(Requirement::GlideJump),
-            "gorlek" => Ok(Requirement::Difficulty(Difficulty::Gorlek)),
             "Grapple" => Ok(Requirement::Skill(Skill::Grapple)),
             "Grenade" => Ok(Requirement::Skill(Skill::Grenade)),
             "GrenadeCancel" => Ok(Requirement::GrenadeCancel),
@@ -289,13 +289,11 @@ fn parse_requirement<'a>(token: &Token<'a>, metadata: &Metadata) -> Result<Requi
             "HammerJump" => Ok(Requirement::HammerJump),
             "HollowTP" => Ok(Requirement::Teleporter(Teleporter::Hollow)),
             "InnerRuinsTP" => Ok(Requirement::Teleporter(Teleporter::InnerRuins)),
-            "kii" => Ok(Requirement::Difficulty(Difficulty::Kii)),
             "Launch" => Ok(Requirement::Skill(Skill::Launch)),
             "LaunchSwap" => Ok(Requirement::LaunchSwap),
             "LifeHarvest" => Ok(Requirement::Shard(Shard::LifeHarvest)),
             "Magnet" => Ok(Requirement::Shard(Shard::Magnet)),
             "MarshTP" => Ok(Requirement::Teleporter(Teleporter::Marsh)),
-            "moki" => Ok(Requirement::Difficulty(Difficulty::Moki)),
             "OuterRuinsTP" => Ok(Requirement::Teleporter(Teleporter::OuterRuins)),
             "Overflow" => Ok(Requirement::Shard(Shard::Overflow)),
             "PauseHover" => Ok(Requirement::Glitch(Glitch::PauseHover)),
@@ -315,7 +313,6 @@ fn parse_requirement<'a>(token: &Token<'a>, metadata: &Metadata) -> Result<Requi
             "Thorn" => Ok(Requirement::Shard(Shard::Thorn)),
             "UltraBash" => Ok(Requirement::Shard(Shard::UltraBash)),
             "UltraGrapple" => Ok(Requirement::Shard(Shard::UltraGrapple)),
-            "unsafe" => Ok(Requirement::Difficulty(Difficulty::Unsafe)),
             "WallJump" => Ok(Requirement::Skill(Skill::WallJump)),
             "WaterBreath" => Ok(Requirement::Skill(Skill::WaterBreath)),
             "WaterDash" => Ok(Requirement::Skill(Skill::WaterDash)),
@@ -326,6 +323,7 @@ fn parse_requirement<'a>(token: &Token<'a>, metadata: &Metadata) -> Result<Requi
             "WestWastesTP" => Ok(Requirement::Teleporter(Teleporter::WestWastes)),
             "WestWoodsTP" => Ok(Requirement::Teleporter(Teleporter::WestWoods)),
             "WillowTP" => Ok(Requirement::Teleporter(Teleporter::Willow)),
+            _ if metadata.difficulty_rank(keyword).is_some() => Ok(Requirement::Difficulty(keyword)),
             _ if metadata.definitions.contains(keyword) => Ok(Requirement::Definition(keyword)),
             _ if metadata.states.contains(keyword) || metadata.quests.contains(keyword) => Ok(Requirement::State(keyword)),
             "BlazeSwap" | "Boss" | "BreakWall" | "Combat" | "Damage" | "Danger" | "Energy" | "GrenadeRedirect" | "Health" | "Keystone" | "Ore" | "SentryBreak" | "SentryBurn" | "SentryJump"| "SentryRedirect" | "SentrySwap" | "SwordSJump" | "HammerSJump" | "ShardSlot" | "ShurikenBreak" | "SpiritLight"
@@ -440,7 +438,7 @@ fn parse_connection<'a>(tokens: &[Token<'a>], position: &mut usize, identifier:
     eat(tokens, position, TokenType::Group)?;
     let requirements = parse_group(tokens, position, metadata)?;
 
-    Ok(Connection { name, identifier, requirements })
+    Ok(Connection { name, identifier, requirements, door: None })
 }
 fn parse_state<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a str, metadata: &Metadata) -> Result<Connection<'a>, ParseError> {
     parse_connection(tokens, position, identifier, metadata, NodeType::State)
@@ -454,6 +452,16 @@ fn parse_pickup<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a
 fn parse_anchor_connection<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a str, metadata: &Metadata) -> Result<Connection<'a>, ParseError> {
     parse_connection(tokens, position, identifier, metadata, NodeType::Anchor)
 }
+fn parse_door<'a>(tokens: &[Token<'a>], token: &Token<'a>, position: &mut usize, metadata: &Metadata) -> Result<Connection<'a>, ParseError> {
+    let mut parts = token.value.split_whitespace();
+    let identifier = parts.next().ok_or_else(|| not_int(token))?;
+    let door = parts.next().ok_or_else(|| not_int(token))?.parse::<i32>().map_err(|_| not_int(token))?;
+    if parts.next().is_some() { return Err(not_int(token)); }
+
+    let mut connection = parse_connection(tokens, position, identifier, metadata, NodeType::Anchor)?;
+    connection.door = Some(door);
+    Ok(connection)
+}
 
 fn parse_anchor<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a str, metadata: &Metadata) -> Result<Anchor<'a>, ParseError> {
     let mut token = next_token!(tokens, *position, TokenType::Position, TokenType::Group);
@@ -482,10 +490,10 @@ fn parse_anchor<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a
     }
     eat(tokens, position, TokenType::Indent)?;
 
-    token = next_token!(tokens, *position, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::NoSpawn, TokenType::Dedent);
+    token = next_token!(tokens, *position, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Door, TokenType::NoSpawn, TokenType::Dedent);
     let can_spawn = if token.name == TokenType::NoSpawn {
         eat(tokens, position, TokenType::Newline)?;
-        token = next_token!(tokens, *position, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Dedent);
+        token = next_token!(tokens, *position, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Door, TokenType::Dedent);
         false
     } else { true };
 
@@ -499,10 +507,11 @@ fn parse_anchor<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a
             TokenType::Quest => connections.push(parse_quest(tokens, position, token.value, metadata)?),
             TokenType::Pickup => connections.push(parse_pickup(tokens, position, token.value, metadata)?),
             TokenType::Connection => connections.push(parse_anchor_connection(tokens, position, token.value, metadata)?),
+            TokenType::Door => connections.push(parse_door(tokens, token, position, metadata)?),
             TokenType::Dedent => return Ok(Anchor { identifier, position: anchor_position, can_spawn, refills, connections }),
-            _ => wrong_token!(token, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Dedent),
+            _ => wrong_token!(token, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Door, TokenType::Dedent),
         }
-        token = next_token!(tokens, *position, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Dedent);
+        token = next_token!(tokens, *position, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Door, TokenType::Dedent);
     }
 }
 
@@ -533,7 +542,7 @@ pub fn parse_areas<'a>(tokens: Vec<Token<'a>>, metadata: &Metadata) -> Result<Ar
                 }
             },
             TokenType::Anchor => anchors.push(parse_anchor(&tokens, &mut position, token.value, metadata)?),
-            TokenType::Newline => {},
+            TokenType::Newline | TokenType::Difficulties => {},
             _ => wrong_token!(token, TokenType::Definition, TokenType::Anchor),
         }
     }
@@ -4,10 +4,14 @@ use rustc_hash::FxHashSet;
 use smallvec::SmallVec;
 
 use crate::auto_display;
+use crate::util::Difficulty;
+
+const BUILTIN_DIFFICULTIES: [Difficulty; 4] = [Difficulty::Moki, Difficulty::Gorlek, Difficulty::Kii, Difficulty::Unsafe];
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TokenType {
     Whitespace,
+    Difficulties,
     Definition,
     Region,
     Anchor,
@@ -20,6 +24,7 @@ pub enum TokenType {
     Quest,
     Pickup,
     Connection,
+    Door,
     Requirement,
     Free,
     Group,
@@ -83,6 +88,18 @@ pub struct Metadata<'a> {
     pub definitions: FxHashSet<&'a str>,
     pub states: FxHashSet<&'a str>,
     pub quests: FxHashSet<&'a str>,
+    /// The difficulty tiers from easiest to hardest
+    pub difficulties: Vec<&'a str>,
+}
+impl Metadata<'_> {
+    pub fn difficulty_rank(&self, name: &str) -> Option<usize> {
+        self.difficulties.iter().position(|&difficulty| difficulty == name)
+    }
+    /// The builtin difficulty whose game mechanics apply on a tier, which is the closest builtin tier at or below it
+    pub fn base_difficulty(&self, name: &str) -> Option<Difficulty> {
+        let rank = self.difficulty_rank(name)?;
+        self.difficulties[..=rank].iter().rev().find_map(|&difficulty| Difficulty::from_name(difficulty))
+    }
 }
 
 #[inline]
@@ -174,6 +191,9 @@ fn tokenize_group<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
 fn tokenize_connection<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
     tokenize_named_key("conn ", TokenType::Connection, None, context)
 }
+fn tokenize_door<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
+    tokenize_named_key("door ", TokenType::Door, Some(|char: char| char == ':' || char == '\n'), context)
+}
 fn tokenize_pickup<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
     tokenize_named_key("pickup ", TokenType::Pickup, None, context)
 }
@@ -195,6 +215,9 @@ fn tokenize_quest<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
 fn tokenize_region<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
     tokenize_named_key("region ", TokenType::Region, None, context)
 }
+fn tokenize_difficulties<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
+    tokenize_named_key("difficulties ", TokenType::Difficulties, Some(|char: char| char == '#' || char == '\n'), context)
+}
 fn tokenize_definition<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>> {
     tokenize_named_key("requirement ", TokenType::Definition, None, context)
 }
@@ -208,7 +231,7 @@ fn tokenize_requirement<'a>(context: &mut TokenContext<'a>) -> Option<Token<'a>>
     tokenize_by_delimiter(TokenType::Requirement, |c: char| c.is_whitespace() || c == ',' || c == ':' || c == '#', context)
 }
 
-const TOKENIZERS: [for<'a> fn(&mut TokenContext<'a>) -> Option<Token<'a>>; 15] = [
+const TOKENIZERS: [for<'a> fn(&mut TokenContext<'a>) -> Option<Token<'a>>; 17] = [
     tokenize_and,           // 8511 occurences
     tokenize_or,            // 5676
     tokenize_group,         // 4301
@@ -222,10 +245,31 @@ const TOKENIZERS: [for<'a> fn(&mut TokenContext<'a>) -> Option<Token<'a>>; 15] =
     tokenize_quest,         // 47
     tokenize_region,        // 20
     tokenize_definition,    // 1
+    tokenize_difficulties,  // 0
     tokenize_nospawn,       // 0
+    tokenize_door,          // 0
     tokenize_requirement,
 ];
 
+fn parse_difficulties(declaration: &str) -> Result<Vec<&str>, String> {
+    let difficulties = declaration.split(',').map(str::trim).collect::<Vec<_>>();
+    for (index, name) in difficulties.iter().enumerate() {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid difficulty name \"{}\"", name));
+        }
+        if difficulties[..index].contains(name) {
+            return Err(format!("Difficulty {} declared more than once", name));
+        }
+    }
+
+    let builtins = difficulties.iter().filter_map(|&name| Difficulty::from_name(name));
+    if Difficulty::from_name(difficulties[0]) != Some(Difficulty::Moki) || !builtins.eq(BUILTIN_DIFFICULTIES.iter().copied()) {
+        return Err(String::from("The difficulties have to start with moki and include gorlek, kii and unsafe in that order"));
+    }
+
+    Ok(difficulties)
+}
+
 pub fn tokenize(input: &str) -> Result<(Vec<Token>, Metadata), String> {
     let mut context = TokenContext::new(input);
     let mut tokens = Vec::with_capacity(input.len() / 9);
@@ -240,6 +284,12 @@ pub fn tokenize(input: &str) -> Result<(Vec<Token>, Metadata), String> {
                     TokenType::Definition => { context.metadata.definitions.insert(token.value); }
                     TokenType::State => { context.metadata.states.insert(token.value); }
                     TokenType::Quest => { context.metadata.quests.insert(token.value); }
+                    TokenType::Difficulties => {
+                        if tokens.iter().any(|token| token.name != TokenType::Newline) {
+                            return Err(format!("Difficulties have to be declared at the top of the file, found them on line {}", context.line));
+                        }
+                        context.metadata.difficulties = parse_difficulties(token.value)?;
+                    }
                     _ => {}
                 }
                 tokens.push(token);
@@ -248,6 +298,9 @@ pub fn tokenize(input: &str) -> Result<(Vec<Token>, Metadata), String> {
         }
         return Err(format!("Failed to read line {}: {}", context.line, context.input.lines().next().unwrap_or("")));
     }
+    if context.metadata.difficulties.is_empty() {
+        context.metadata.difficulties = BUILTIN_DIFFICULTIES.iter().map(|difficulty| difficulty.name()).collect();
+    }
 
     Ok((tokens, context.metadata))
 }
@@ -4,17 +4,22 @@ pub mod inventory;
 pub mod item;
 pub mod settings;
 pub mod generator;
+pub mod spoiler;
 pub mod util;
 
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+};
 
 use rand_seeder::Seeder;
 use rand::{
     Rng,
     rngs::StdRng,
     distributions::{Distribution, Uniform},
-    seq::IteratorRandom
+    seq::{IteratorRandom, SliceRandom},
 };
+use rayon::prelude::*;
 
 use log::LevelFilter;
 use log4rs::{
@@ -37,25 +42,27 @@ use world::{
     pool::Pool
 };
 use generator::Placement;
+use item::{Item, Resource};
 use languages::headers::parser::HeaderContext;
 use settings::{Settings, Spawn};
 use util::{
     Difficulty, Position, Zone, UberState, Icon,
-    constants::{DEFAULT_SPAWN, MOKI_SPAWNS, GORLEK_SPAWNS, SPAWN_GRANTS, RETRIES},
+    constants::{DEFAULT_SPAWN, MOKI_SPAWNS, GORLEK_SPAWNS, SPAWN_GRANTS},
 };
 
 use crate::languages::headers;
 
-fn pick_spawn<'a, R>(graph: &'a Graph, settings: &Settings, rng: &mut R) -> Result<&'a Node, String>
+fn pick_spawn<'a, R>(world: &World<'a>, settings: &Settings, rng: &mut R) -> Result<&'a Node, String>
 where
     R: Rng
 {
+    let graph = world.graph;
     let mut valid = graph.nodes.iter().filter(|node| node.can_spawn());
     let spawn = match &settings.spawn_loc {
         Spawn::Random => valid
             .filter(|&node| {
                 let identifier = node.identifier();
-                if settings.difficulty >= Difficulty::Gorlek {
+                if world.player.difficulty >= Difficulty::Gorlek {
                     GORLEK_SPAWNS.contains(&identifier)
                 } else {
                     MOKI_SPAWNS.contains(&identifier)
@@ -63,16 +70,87 @@ where
             })
             .choose(rng)
             .ok_or_else(|| String::from("No valid spawn locations available"))?,
-        Spawn::FullyRandom => valid
-            .choose(rng)
-            .ok_or_else(|| String::from("No valid spawn locations available"))?,
+        Spawn::FullyRandom => {
+            // many of the spawn candidates are in areas which can't be left again early on
+            let mut candidates = valid.collect::<Vec<_>>();
+            candidates.shuffle(rng);
+            candidates.into_iter()
+                .find(|&node| match graph.traps(&world.player, node, &world.uber_states, &world.sets) {
+                    Ok(traps) if traps.is_empty() => true,
+                    Ok(traps) => {
+                        log::trace!("Rejecting spawn {} because it could softlock in {}", node, traps.iter().map(|trap| trap.identifier()).collect::<Vec<_>>().join(", "));
+                        false
+                    },
+                    Err(_) => false,
+                })
+                .ok_or_else(|| String::from("No valid spawn locations available"))?
+        },
         Spawn::Set(spawn_loc) => valid
             .find(|&node| node.identifier() == spawn_loc)
-            .ok_or_else(|| format!("Spawn {} not found", spawn_loc))?
+            .ok_or_else(|| format!("Spawn {} not found", spawn_loc))?,
+        Spawn::Pool(spawn_pool) => {
+            let (spawn_loc, _) = spawn_pool.choose_weighted(rng, |&(_, weight)| weight)
+                .map_err(|err| format!("Failed to choose from the spawn pool: {}", err))?;
+            valid
+                .find(|&node| node.identifier() == spawn_loc)
+                .ok_or_else(|| format!("Spawn {} not found", spawn_loc))?
+        },
     };
     Ok(spawn)
 }
 
+fn validate_spawn(graph: &Graph, spawn: &Spawn) -> Result<(), String> {
+    if let Spawn::Pool(spawn_pool) = spawn {
+        if spawn_pool.is_empty() {
+            return Err(String::from("The spawn pool is empty"));
+        }
+        if !spawn_pool.iter().any(|&(_, weight)| weight > 0.0) {
+            return Err(String::from("The spawn pool needs at least one spawn with a positive weight"));
+        }
+
+        for (spawn_loc, weight) in spawn_pool {
+            if !(weight.is_finite() && *weight >= 0.0) {
+                return Err(format!("Invalid weight {} for {} in the spawn pool", weight, spawn_loc));
+            }
+            let node = graph.nodes.iter().find(|&node| node.identifier() == spawn_loc).ok_or_else(|| format!("Spawn {} not found", spawn_loc))?;
+            if !node.can_spawn() {
+                return Err(format!("{} can't be used as a spawn location", spawn_loc));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// the keystone pool matches the vanilla door costs, so it has to follow any changes to them
+// headers might have removed the keystones along with the doors, in that case there is nothing to adjust
+fn adjust_keystone_pool(world: &mut World, graph: &Graph) {
+    let keystone = Item::Resource(Resource::Keystone);
+    if world.pool.inventory.get(&keystone) == 0 { return; }
+
+    let (vanilla, randomized) = graph.keystone_doors.iter().fold((0, 0), |(vanilla, randomized), &(door_index, cost)| {
+        let door = &graph.nodes[door_index];
+        (vanilla + world.graph.keystone_cost(door).unwrap_or(0), randomized + cost)
+    });
+
+    if randomized > vanilla {
+        world.pool.grant(keystone, randomized - vanilla);
+    } else {
+        world.pool.remove(&keystone, vanilla - randomized);
+    }
+    log::trace!("Adjusted the keystone pool by {} for randomized door costs", i32::from(randomized) - i32::from(vanilla));
+}
+
+fn parse_starting_items(settings: &Settings) -> Result<Vec<(Item, u16)>, String> {
+    settings.starting_items.iter().map(|starting_item| {
+        let mut item = starting_item.as_str();
+        let amount = headers::parser::parse_count(&mut item);
+        let item = headers::parser::parse_item(item).map_err(|err| format!("Invalid starting item: {}", err))?;
+
+        Ok((item, amount))
+    }).collect()
+}
+
 pub fn write_flags(settings: &Settings, mut flags: Vec<String>) -> String {
     let mut settings_flags = Vec::new();
 
@@ -80,7 +158,7 @@ pub fn write_flags(settings: &Settings, mut flags: Vec<String>) -> String {
         settings_flags.push(flag);
     }
 
-    if matches!(settings.spawn_loc, Spawn::Random | Spawn::FullyRandom) { settings_flags.push(String::from("RandomSpawn")); }
+    if matches!(settings.spawn_loc, Spawn::Random | Spawn::FullyRandom | Spawn::Pool(_)) { settings_flags.push(String::from("RandomSpawn")); }
 
     settings_flags.append(&mut flags);
 
@@ -138,7 +216,7 @@ pub fn initialize_log(use_file: Option<&str>, stderr_log_level: LevelFilter, jso
     Ok(())
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ItemDetails {
     name: Option<String>,
     display: Option<String>,
@@ -215,53 +293,79 @@ where R: Rng + ?Sized
         }
     }
 
-    for (item, amount) in context.negative_inventory.items {
+    for (item, amount) in context.negative_inventory.iter() {
         world.pool.inventory.remove(&item, amount);
     }
 
     Ok((header_block, context.flags, context.custom_items, context.sets))
 }
 
-fn generate_placements<'a, R>(
-    graph: &'a Graph,
-    worlds: Vec<World<'a>>,
+fn generate_placements<'a>(
+    worlds: &[World<'a>],
+    world_settings: &[Settings],
     settings: &Settings,
     spawn_pickup_node: &'a Node,
-    custom_items: &HashMap<String, ItemDetails>,
-    rng: &mut R
-) -> Result<(Vec<Vec<Placement<'a>>>, Vec<&'a Node>), String>
-where R: Rng
-{
-    let mut index = 0;
-    loop {
-        let spawn_locs = (0..settings.worlds)
-            .map(|_| pick_spawn(graph, settings, rng))
+    custom_items: &[HashMap<String, ItemDetails>],
+    seed: &str
+) -> Result<(Vec<Vec<Placement<'a>>>, Vec<&'a Node>), String> {
+    settings.generator.validate()?;
+    for (world, world_settings) in worlds.iter().zip(world_settings) {
+        validate_spawn(world.graph, &world_settings.spawn_loc)?;
+    }
+    generator::verify_custom_goals(worlds, settings)?;
+    generator::warn_unknown_exclusions(worlds[0].graph, settings);
+    let constraints = generator::parse_constraints(worlds[0].graph, settings)?;
+
+    let retries = settings.generator.retries;
+
+    // every attempt gets its own rng derived from the seed, and the first attempt that succeeds wins, so the result doesn't depend on how many threads are available
+    let attempt = |index: u16| -> Result<(Vec<Vec<Placement<'a>>>, Vec<&'a Node>), String> {
+        let mut rng: StdRng = Seeder::from(format!("{}_{}", seed, index)).make_rng();
+
+        let spawn_locs = worlds.iter().zip(world_settings)
+            .map(|(world, world_settings)| pick_spawn(world, world_settings, &mut rng))
             .collect::<Result<Vec<_>, String>>()?;
         let identifiers = spawn_locs.iter().map(|spawn_loc| spawn_loc.identifier()).collect::<Vec<_>>();
-        log::trace!("Spawning on {}", identifiers.join(", "));
+        log::trace!("Attempt {}: Spawning on {}", index + 1, identifiers.join(", "));
 
-        match generator::generate_placements(worlds.clone(), &spawn_locs, spawn_pickup_node, custom_items, settings, rng) {
-            Ok(seed) => {
-                if index > 0 {
-                    log::info!("Generated seed after {} tries{}", index + 1, if index < RETRIES / 2 { "" } else { " (phew)" });
-                }
-                return Ok((seed, spawn_locs));
-            },
-            Err(err) => log::error!("{}\nRetrying...", err),
-        }
+        let placements = generator::generate_placements(worlds.to_vec(), &spawn_locs, spawn_pickup_node, custom_items, &constraints, settings, &mut rng)
+            .map_err(|err| {
+                log::error!("Attempt {} failed: {}", index + 1, err);
+                err
+            })?;
 
-        index += 1;
-        if index == RETRIES {
-            return Err(format!("All {} attempts to generate a seed failed :(", RETRIES));
-        }
+        Ok((placements, spawn_locs))
     };
+
+    // run as many attempts at once as there are threads, so no work is wasted if the first one succeeds on a single thread
+    let batch_size = u16::try_from(rayon::current_num_threads()).unwrap_or(u16::MAX).max(1);
+    let mut batch_start = 0;
+    while batch_start < retries {
+        let batch_end = retries.min(batch_start.saturating_add(batch_size));
+
+        if let Some((index, seed)) = (batch_start..batch_end).into_par_iter()
+            .map(|index| (index, attempt(index)))
+            .find_first(|(_, result)| result.is_ok())
+        {
+            if index > 0 {
+                log::info!("Generated seed after {} tries{}", index + 1, if index < retries / 2 { "" } else { " (phew)" });
+            }
+            return seed;
+        }
+
+        batch_start = batch_end;
+    }
+
+    Err(format!("All {} attempts to generate a seed failed :(", retries))
 }
 
 #[inline]
-fn format_placements(world_placements: Vec<Placement>, custom_items: &HashMap<String, ItemDetails>, race: bool) -> String {
+fn format_placements(world_placements: Vec<Placement>, graph: &Graph, custom_items: &HashMap<String, ItemDetails>, race: bool) -> String {
     let mut placement_block = String::with_capacity(world_placements.len() * 20);
+    let spirit_light_total = generator::spirit_light_total(&world_placements);
 
-    for placement in world_placements {
+    for mut placement in world_placements {
+        placement.item = graph.seed_item(&placement.item);
         let mut placement_line = format!("{}", placement);
 
         if !race {
@@ -289,14 +393,37 @@ fn format_placements(world_placements: Vec<Placement>, custom_items: &HashMap<St
         placement_block.push_str(&placement_line);
     }
 
+    if !race {
+        placement_block += &format!("// Spirit Light total: {}\n", spirit_light_total);
+    }
+
     placement_block
 }
 
 type Seeds = Vec<String>;
 type Spoilers = Vec<String>;
-pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String], seed: Option<String>) -> Result<(Seeds, Spoilers), String> {
+type JsonSpoilers = Vec<String>;
+pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String], seed: Option<String>, json_spoiler: bool) -> Result<(Seeds, Spoilers, JsonSpoilers), String> {
+    let settings = settings.apply_presets()?;
+    if settings.has_world_logic() {
+        return Err(String::from("Worlds with their own difficulty or glitches need their own logic, use generate_multiworld_seed instead"));
+    }
+
+    let graphs = vec![graph; settings.worlds];
+    generate_multiworld_seed(&graphs, settings, inline_headers, seed, json_spoiler)
+}
+
+/// Generate a seed where every world uses its own logic, `graphs` has to hold the graph for each world emitted with that world's settings
+pub fn generate_multiworld_seed(graphs: &[&Graph], settings: Settings, inline_headers: &[String], seed: Option<String>, json_spoiler: bool) -> Result<(Seeds, Spoilers, JsonSpoilers), String> {
     let mut settings = settings.apply_presets()?;
 
+    if graphs.len() != settings.worlds {
+        return Err(format!("Expected logic for {} worlds, but got {}", settings.worlds, graphs.len()));
+    }
+    if settings.world_settings.len() > settings.worlds {
+        return Err(format!("Got world settings for {} worlds, but there are only {}", settings.world_settings.len(), settings.worlds));
+    }
+
     let seed = seed.unwrap_or_else(|| {
         let mut generated_seed = String::new();
         let numeric = Uniform::from('0'..='9');
@@ -317,19 +444,78 @@ pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String
     let mut rng: StdRng = Seeder::from(&seed).make_rng();
     log::trace!("Seeded RNG with {}", seed);
 
-    let mut world = World::new(graph);
-    world.pool = Pool::preset();
-    world.player.spawn(&settings);
+    let starting_items = parse_starting_items(&settings)?;
+    let mut starting_items_block = String::new();
+    for (item, amount) in &starting_items {
+        log::trace!("Granting {}{} on spawn", if *amount == 1 { String::new() } else { format!("{}x ", amount) }, item);
+
+        for _ in 0..*amount {
+            starting_items_block += &format!("{}|{}|mute\n", UberState::spawn(), item.code());
+        }
+    }
+
+    let world_settings = (0..settings.worlds).map(|world_index| settings.for_world(world_index)).collect::<Vec<_>>();
+
+    let mut worlds = Vec::<World>::with_capacity(settings.worlds);
+    let mut header_blocks = Vec::<String>::with_capacity(settings.worlds);
+    let mut flag_lines = Vec::<String>::with_capacity(settings.worlds);
+    let mut world_sets = Vec::<Sets>::with_capacity(settings.worlds);
+    let mut custom_items = Vec::<HashMap<String, ItemDetails>>::with_capacity(settings.worlds);
+    for world_index in 0..settings.worlds {
+        // worlds without their own settings share the result of parsing the headers
+        let overrides = settings.world_settings.get(world_index).cloned().unwrap_or_default();
+        if let Some(same_index) = (0..world_index).find(|&other_index| settings.world_settings.get(other_index).cloned().unwrap_or_default() == overrides) {
+            worlds.push(worlds[same_index].clone());
+            header_blocks.push(header_blocks[same_index].clone());
+            flag_lines.push(flag_lines[same_index].clone());
+            world_sets.push(world_sets[same_index].clone());
+            custom_items.push(custom_items[same_index].clone());
+            continue;
+        }
+
+        let mut world = World::new(graphs[world_index]);
+        world.pool = Pool::preset();
+        world.player.spawn(&world_settings[world_index]);
 
-    let (header_block, custom_flags, custom_items, sets) = parse_headers(&mut world, inline_headers, &settings, &mut rng)?;
+        let (header_block, custom_flags, world_custom_items, sets) = parse_headers(&mut world, inline_headers, &world_settings[world_index], &mut rng)?;
+        custom_items.push(world_custom_items);
 
-    let flag_line = write_flags(&settings, custom_flags);
+        for (item, amount) in &starting_items {
+            world.pool.remove(item, *amount);
+            for _ in 0..*amount {
+                world.preplace(UberState::spawn(), item.clone());
+            }
+        }
 
-    let mut worlds = vec![world];
-    for _ in 1..settings.worlds {
-        worlds.push(worlds[0].clone());
+        worlds.push(world);
+        header_blocks.push(header_block);
+        flag_lines.push(write_flags(&world_settings[world_index], custom_flags));
+        world_sets.push(sets);
     }
 
+    // worlds with randomized keystone doors or entrances need their own copy of the logic, which has to outlive the worlds referring to it
+    let own_graphs = if settings.keystone_costs.is_some() || settings.randomize_teleporters || settings.randomize_doors {
+        worlds.iter_mut().enumerate().map(|(world_index, world)| {
+            let player_name = settings.players.get(world_index).cloned().unwrap_or_else(|| format!("Player {}", world_index + 1));
+            let mut graph = world.graph.clone();
+
+            if let Some(keystone_costs) = settings.keystone_costs {
+                graph.randomize_keystone_costs(keystone_costs, &mut rng)?;
+                adjust_keystone_pool(world, &graph);
+            }
+            if settings.randomize_teleporters || settings.randomize_doors {
+                let shuffled = generator::randomize_entrances(&World { graph: &graph, ..world.clone() }, &player_name, &settings, &mut rng)?;
+                graph = shuffled;
+            }
+
+            Ok(graph)
+        }).collect::<Result<Vec<_>, String>>()?
+    } else { Vec::new() };
+    let worlds = if own_graphs.is_empty() { worlds } else {
+        worlds.into_iter().zip(&own_graphs).map(|(world, graph)| World { graph, ..world }).collect()
+    };
+    let world_graphs = worlds.iter().map(|world| world.graph).collect::<Vec<_>>();
+
     let spawn_pickup_node = Node::Pickup(Pickup {
         identifier: String::from("Spawn"),
         zone: Zone::Spawn,
@@ -338,7 +524,14 @@ pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String
         position: Position::default(),
     });
 
-    let (placements, spawn_locs) = generate_placements(graph, worlds, &settings, &spawn_pickup_node, &custom_items, &mut rng)?;
+    let (placements, spawn_locs) = generate_placements(&worlds, &world_settings, &settings, &spawn_pickup_node, &custom_items, &seed)?;
+
+    let json_spoilers = if json_spoiler {
+        spoiler::generate_spoilers(worlds, &spawn_locs, &placements, &custom_items, &settings)?
+            .iter()
+            .map(spoiler::WorldSpoiler::write)
+            .collect::<Result<Vec<_>, String>>()?
+    } else { Vec::new() };
 
     let spawn_lines = spawn_locs.into_iter().map(|spawn_loc| {
         let identifier = spawn_loc.identifier();
@@ -350,33 +543,37 @@ pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String
             }
 
             let position = spawn_loc.position().ok_or_else(|| format!("Tried to spawn on {} which has no specified coordinates", identifier))?;
-            return Ok(format!("Spawn: {}  // {}\n{}", position, identifier, spawn_item));
+            return Ok(format!("Spawn: {}  // {}\n{}{}", position, identifier, spawn_item, starting_items_block));
         }
-        Ok(String::new())
+        Ok(starting_items_block.clone())
     }).collect::<Result<Vec<_>, String>>()?;
 
     let spoiler_blocks = if settings.race {
         Some(placements.iter()
-            .map(|world_placements| format_placements(world_placements.clone(), &custom_items, false))
+            .zip(&world_graphs)
+            .zip(&custom_items)
+            .map(|((world_placements, graph), world_custom_items)| format_placements(world_placements.clone(), graph, world_custom_items, false))
             .collect::<Vec<_>>())
     } else { None };
     let placement_blocks = placements.into_iter()
-        .map(|world_placements| format_placements(world_placements, &custom_items, settings.race))
+        .zip(&world_graphs)
+        .zip(&custom_items)
+        .map(|((world_placements, graph), world_custom_items)| format_placements(world_placements, graph, world_custom_items, settings.race))
         .collect::<Vec<_>>();
 
     let slug_line = format!("// Slug: {}", slug);
     let seed_line = format!("// Seed: {}", seed);
-    let set_line = if sets.is_empty() {
+    let set_lines = world_sets.iter().map(|sets| if sets.is_empty() {
         String::new()
     } else {
         format!("// Sets: {}", sets.join(", "))
-    };
+    }).collect::<Vec<_>>();
     let config_line = format!("// Config: {}", config);
 
     let mut seeds = (0..settings.worlds).map(|index| {
-        format!("{}{}\n{}\n{}{}\n{}\n{}\n{}", flag_line, spawn_lines[index], placement_blocks[index], header_block, slug_line, seed_line, set_line, config_line)
+        format!("{}{}\n{}\n{}{}\n{}\n{}\n{}", flag_lines[index], spawn_lines[index], placement_blocks[index], header_blocks[index], slug_line, seed_line, set_lines[index], config_line)
     }).collect::<Vec<_>>();
-    headers::parser::postprocess(&mut seeds, graph, &settings)?;
+    headers::parser::postprocess(&mut seeds, &world_graphs, &settings)?;
 
     let spoilers = spoiler_blocks.map_or_else::<Result<_, String>, _, _>(
         || Ok(Vec::new()),
@@ -387,43 +584,70 @@ pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String
             let spoiler_config_line = format!("// Config: {}", spoiler_config);
 
             let mut spoiler_seeds = (0..settings.worlds).map(|index| {
-                format!("{}{}\n{}\n{}{}\n{}\n{}\n{}", flag_line, spawn_lines[index], spoiler_blocks[index], header_block, slug_line, seed_line, set_line, spoiler_config_line)
+                format!("{}{}\n{}\n{}{}\n{}\n{}\n{}", flag_lines[index], spawn_lines[index], spoiler_blocks[index], header_blocks[index], slug_line, seed_line, set_lines[index], spoiler_config_line)
             }).collect::<Vec<_>>();
-            headers::parser::postprocess(&mut spoiler_seeds, graph, &settings)?;
+            headers::parser::postprocess(&mut spoiler_seeds, &world_graphs, &settings)?;
 
             Ok(spoiler_seeds)
         })?;
 
-    Ok((seeds, spoilers))
+    Ok((seeds, spoilers, json_spoilers))
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{fs, path::PathBuf};
 
     use super::*;
+    use settings::{PlacementConstraint, Constraint, WorldSettings};
+    use item::{Teleporter, Command};
+    use world::graph::DoorRedirect;
+    use util::{GoalMode, CustomGoal, constants::{KEYSTONE_DOORS, KEYSTONE_COST_GROUP}};
+
+    fn spawn_pickup_node() -> Node {
+        Node::Pickup(Pickup {
+            identifier: String::from("Spawn"),
+            zone: Zone::Spawn,
+            index: usize::MAX,
+            uber_state: UberState::spawn(),
+            position: Position::default(),
+        })
+    }
+
+    fn test_logic(settings: &Settings) -> Graph {
+        languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", settings, false).unwrap()
+    }
+    fn test_seeds(graph: &Graph, settings: Settings) -> Seeds {
+        let (seeds, _, _) = generate_seed(graph, settings, &Vec::new(), None, false).unwrap();
+        seeds
+    }
+    // the item a line of the seed grants, leaving out the uber state it triggers on
+    fn item_code(line: &str) -> &str {
+        let item_code = line.splitn(3, '|').nth(2).unwrap().split_whitespace().next().unwrap();
+        item_code.strip_suffix("|mute").unwrap_or(item_code)
+    }
 
     #[test]
     fn some_seeds() {
         initialize_log(Some("generator.log"), LevelFilter::Off, false).unwrap();
 
         let mut settings = Settings::default();
-        let mut graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut graph = test_logic(&settings);
 
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        test_seeds(&graph, settings.clone());
 
         settings.hard = true;
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        test_seeds(&graph, settings.clone());
 
         settings.hard = false;
-        settings.difficulty = Difficulty::Unsafe;
-        graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        settings.difficulty = String::from("unsafe");
+        graph = test_logic(&settings);
+        test_seeds(&graph, settings.clone());
 
-        settings.difficulty = Difficulty::Gorlek;
-        graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        settings.difficulty = String::from("gorlek");
+        graph = test_logic(&settings);
         settings.presets = vec![PathBuf::from("gorlek")];
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        test_seeds(&graph, settings.clone());
 
         settings.presets.push(PathBuf::from("rspawn"));
         settings.header_list = vec![
@@ -442,11 +666,417 @@ mod tests {
             PathBuf::from("vanilla_opher_upgrades"),
             PathBuf::from("bonus_opher_upgrades"),
         ];
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        test_seeds(&graph, settings.clone());
 
         settings = Settings::default();
-        graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        graph = test_logic(&settings);
         settings.worlds = 5;
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        test_seeds(&graph, settings.clone());
+    }
+
+    #[test]
+    fn excluded_locations() {
+        let mut settings = Settings::default();
+        settings.exclude = vec![String::from("Wastes"), String::from("MarshSpawn.RockHC")];
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings);
+
+        for line in seeds[0].lines().filter(|line| line.ends_with("Wastes") || line.contains("MarshSpawn.RockHC ")) {
+            let item_code = item_code(line);
+            assert!(item_code.starts_with("0|") || item_code == "1|2", "excluded location holds {}", line);
+        }
+    }
+
+    #[test]
+    fn shop_budget() {
+        let mut settings = Settings::default();
+        settings.shop_budget = Some(2000);
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings);
+
+        let shop_prices = seeds[0].lines()
+            .filter_map(|line| line.strip_prefix("3|1|8|"))
+            .filter_map(|setter| {
+                let parts = setter.split_whitespace().next().unwrap().split('|').collect::<Vec<_>>();
+                if parts[2] == "int" { parts[3].parse::<u32>().ok() } else { None }
+            })
+            .collect::<Vec<_>>();
+        assert!(!shop_prices.is_empty());
+        assert!(shop_prices.iter().sum::<u32>() <= 2000);
+
+        // prices set by a header can't be scaled down, so going over the budget with them fails the attempt
+        let mut settings = Settings::default();
+        settings.shop_budget = Some(2000);
+        settings.generator.retries = 1;
+        let header = String::from("!!price 1|2 1000\n!!price 1|3 1000\n!!price 1|0 1000\n!!price 1|1 1000\n");
+        assert!(generate_seed(&graph, settings, &[header], Some(String::from("0")), false).is_err());
+    }
+
+    #[test]
+    fn spirit_light_totals() {
+        let mut settings = Settings::default();
+        settings.worlds = 3;
+        let graph = test_logic(&settings);
+
+        let spirit_light_amounts = |seed: &str| seed.lines()
+            .filter(|line| line.contains("  // "))
+            .filter_map(|line| {
+                let parts = line.split_whitespace().next().unwrap().split('|').collect::<Vec<_>>();
+                if parts[2] == "0" { parts[3].parse::<u32>().ok() } else { None }
+            })
+            .collect::<Vec<_>>();
+
+        let seeds = test_seeds(&graph, settings.clone());
+        for seed in seeds {
+            let amounts = spirit_light_amounts(&seed);
+            assert!(!amounts.contains(&0));
+            assert_eq!(amounts.iter().sum::<u32>(), 20000);
+            assert!(seed.contains("// Spirit Light total: 20000\n"));
+        }
+
+        // less Spirit Light than there are slots for it
+        let (seeds, _, _) = generate_seed(&graph, settings, &[String::from("!!remove 0|19900")], None, false).unwrap();
+        for seed in seeds {
+            let amounts = spirit_light_amounts(&seed);
+            assert!(!amounts.contains(&0));
+            assert_eq!(amounts.iter().sum::<u32>(), 100);
+        }
+    }
+
+    #[test]
+    fn generator_config() {
+        let mut settings = Settings::default();
+        settings.generator.placeholder_slots = 15;
+        settings.generator.reserve_slots = 2;
+        settings.generator.random_progression = 0.6;
+        settings.generator.price_range = (1.0, 1.0);
+        let generator_config = settings.generator.clone();
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings);
+        assert_eq!(Settings::from_seed(&seeds[0]).unwrap().generator, generator_config);
+
+        let mut settings = Settings::default();
+        settings.generator.spirit_light_range = (1.25, 0.75);
+        assert!(generate_seed(&graph, settings, &Vec::new(), None, false).is_err());
+    }
+
+    #[test]
+    fn progression_balancing() {
+        let mut settings = Settings::default();
+        settings.worlds = 3;
+        settings.generator.progression_balancing = 1.0;
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings);
+        assert_eq!(seeds.len(), 3);
+
+        let mut settings = Settings::default();
+        settings.generator.progression_balancing = 1.5;
+        assert!(generate_seed(&graph, settings, &Vec::new(), None, false).is_err());
+    }
+
+    #[test]
+    fn balanced_spheres() {
+        let settings = Settings { worlds: 2, ..Settings::default() };
+        let graph = test_logic(&settings);
+
+        let mut world = World::new(&graph);
+        world.pool = Pool::preset();
+        world.player.spawn(&settings);
+        let worlds = vec![world; 2];
+        let custom_items = vec![HashMap::new(); 2];
+        let spawn_pickup_node = spawn_pickup_node();
+
+        // how far apart the worlds are in the amount of placements they can collect, summed over the first spheres
+        let sphere_gap = |progression_balancing: f32| -> usize {
+            let mut settings = settings.clone();
+            settings.generator.progression_balancing = progression_balancing;
+            let world_settings = vec![settings.clone(); 2];
+
+            (0..5).map(|seed| {
+                let (placements, spawns) = generate_placements(&worlds, &world_settings, &settings, &spawn_pickup_node, &custom_items, &seed.to_string()).unwrap();
+                let (spheres, _) = spoiler::spheres(worlds.clone(), &spawns, &placements).unwrap();
+
+                let mut collected = [0, 0];
+                spheres.iter().take(8).map(|sphere| {
+                    for (world_collected, world_sphere) in collected.iter_mut().zip(sphere) {
+                        *world_collected += world_sphere.len();
+                    }
+                    collected[0].abs_diff(collected[1])
+                }).sum::<usize>()
+            }).sum()
+        };
+
+        assert!(sphere_gap(1.0) < sphere_gap(0.0));
+    }
+
+    #[test]
+    fn softlocks() {
+        let mut settings = Settings::default();
+        settings.spawn_loc = Spawn::FullyRandom;
+        settings.generator.retries = 30;  // plenty of random spawns can't reach enough to place anything
+        let graph = test_logic(&settings);
+        let mut world = World::new(&graph);
+        world.player.apply_settings(&settings);
+
+        for seed in 0..3 {
+            let (seeds, _, _) = generate_seed(&graph, settings.clone(), &Vec::new(), Some(seed.to_string()), false).unwrap();
+            let spawn = graph.find_spawn(&settings::read_spawn(&seeds[0]).unwrap()).unwrap();
+            assert!(graph.traps(&world.player, spawn, &world.uber_states, &world.sets).unwrap().is_empty());
+        }
+
+        // progressions which could softlock are only forced if nothing else remains, unless the check is turned off
+        assert!(settings.generator.avoid_softlocks);
+        let mut settings = Settings::default();
+        settings.generator.avoid_softlocks = false;
+        generate_seed(&graph, settings, &Vec::new(), Some(String::from("0")), false).unwrap();
+    }
+
+    #[test]
+    fn random_teleporters() {
+        use rand::SeedableRng;
+
+        let mut settings = Settings::default();
+        settings.randomize_teleporters = true;
+        let graph = test_logic(&settings);
+
+        let mut world = World::new(&graph);
+        world.pool = Pool::preset();
+        world.player.spawn(&settings);
+        let teleporters = [Teleporter::Den, Teleporter::Hollow, Teleporter::Glades, Teleporter::Wellspring, Teleporter::Burrows, Teleporter::WestWoods, Teleporter::EastWoods, Teleporter::Reach];
+        for teleporter in teleporters {
+            world.pool.grant(Item::Teleporter(teleporter), 1);
+        }
+
+        let shuffled = generator::randomize_entrances(&world, "Player 1", &settings, &mut StdRng::seed_from_u64(0)).unwrap();
+        let worlds = vec![World { graph: &shuffled, ..world }];
+        let spawn_pickup_node = spawn_pickup_node();
+        let (mut placements, _) = generate_placements(&worlds, &[settings.clone()], &settings, &spawn_pickup_node, &[HashMap::new()], "0").unwrap();
+        let world_placements = placements.pop().unwrap();
+        let placement_block = format_placements(world_placements.clone(), &shuffled, &HashMap::new(), false);
+
+        // the randomizer has to grant the teleporter leading where the logic expects the placed one to lead
+        let mut redirected = 0;
+        for (placement, line) in world_placements.iter().zip(placement_block.lines()) {
+            if let Item::Teleporter(access) = placement.item {
+                let destination = shuffled.teleporter_destination(access);
+                if access != destination { redirected += 1; }
+                assert!(line.starts_with(&format!("{}|{} ", placement.uber_state, Item::Teleporter(destination).code())), "{} should grant {}", line, destination);
+                assert!(line.contains(&format!(" {} from ", destination)));
+            }
+        }
+        assert!(redirected > 0);
+
+        // the sending world names the teleporter the receiving world gets
+        let worlds = vec![worlds[0].clone(); 2];
+        let multiworld_settings = Settings { worlds: 2, ..settings.clone() };
+        let (placements, _) = generate_placements(&worlds, &[settings.clone(), settings.clone()], &multiworld_settings, &spawn_pickup_node, &[HashMap::new(), HashMap::new()], "0").unwrap();
+        let mut sent = 0;
+        for (world_index, world_placements) in placements.iter().enumerate() {
+            for placement in world_placements.iter().filter(|placement| placement.node.is_none() && placement.uber_state.identifier.uber_group == 12) {
+                if let Item::Teleporter(access) = placement.item {
+                    sent += 1;
+                    let message = Item::Message(format!("Player {}'s $[{}]", world_index + 1, Item::Teleporter(shuffled.teleporter_destination(access)).code()));
+                    assert!(placements[1 - world_index].iter().any(|placement| placement.item == message), "missing {}", message);
+                }
+            }
+        }
+        assert!(sent > 0);
+
+        // shuffled doors get redirected on load
+        let mut shuffled = shuffled.clone();
+        shuffled.door_redirects.push(DoorRedirect { intercept: 1, set: 2 });
+        let worlds = vec![World { graph: &shuffled, ..worlds[0].clone() }];
+        let (mut placements, _) = generate_placements(&worlds, &[settings.clone()], &settings, &spawn_pickup_node, &[HashMap::new()], "0").unwrap();
+        let placement_block = format_placements(placements.pop().unwrap(), &shuffled, &HashMap::new(), false);
+        assert!(placement_block.lines().any(|line| line.starts_with(&format!("{}|{} ", UberState::load(), Item::Command(Command::StateRedirect { intercept: 1, set: 2 }).code()))));
+
+        settings.worlds = 2;
+        settings.randomize_doors = true;
+        settings.header_list = vec![PathBuf::from("teleporters")];
+        test_seeds(&graph, settings);
+    }
+
+    #[test]
+    fn keystone_costs() {
+        let mut settings = Settings::default();
+        settings.keystone_costs = Some((3, 3));
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings);
+        let cost_lines = seeds[0].lines().filter(|line| line.starts_with(&format!("{}|8|{}|", UberState::load(), KEYSTONE_COST_GROUP))).collect::<Vec<_>>();
+        assert_eq!(cost_lines.len(), KEYSTONE_DOORS.len());
+        assert!(cost_lines.iter().all(|line| line.split("  //").next().unwrap().trim_end().ends_with("|int|3")));
+        assert_eq!(seeds[0].lines().filter(|line| line.contains(" Keystone from ")).count(), KEYSTONE_DOORS.len() * 3);
+
+        // the costs would overwrite whatever a header stores in the same uber group
+        let group_prefix = format!("{}|", KEYSTONE_COST_GROUP);
+        for entry in fs::read_dir("headers").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().map_or(true, |extension| extension != "wotwrh") { continue; }
+
+            let header = fs::read_to_string(&path).unwrap();
+            let uses_group = header.match_indices(&group_prefix).any(|(index, _)| !header[..index].ends_with(|c: char| c.is_ascii_digit()));
+            assert!(!uses_group, "{} uses the uber group {} reserved for keystone costs", path.display(), KEYSTONE_COST_GROUP);
+        }
+    }
+
+    #[test]
+    fn spawn_pool() {
+        let mut settings = Settings::default();
+        settings.worlds = 2;
+        settings.spawn_loc = Spawn::Pool(vec![(String::from("HowlsDen.Teleporter"), 0.0), (String::from("GladesTown.Teleporter"), 2.0)]);
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings.clone());
+        for seed in seeds {
+            assert!(seed.contains("  // GladesTown.Teleporter\n"));
+        }
+
+        for spawn_pool in [vec![], vec![(String::from("GladesTown.Teleporter"), 0.0)], vec![(String::from("Teleporters"), 1.0)], vec![(String::from("Nowhere"), 1.0)]] {
+            settings.spawn_loc = Spawn::Pool(spawn_pool);
+            assert!(generate_seed(&graph, settings.clone(), &Vec::new(), None, false).is_err());
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let mut settings = Settings::default();
+        settings.worlds = 2;
+        let graph = test_logic(&settings);
+
+        let (seeds, _, _) = generate_seed(&graph, settings.clone(), &Vec::new(), Some(String::from("reproducible")), false).unwrap();
+        let (other_seeds, _, _) = generate_seed(&graph, settings, &Vec::new(), Some(String::from("reproducible")), false).unwrap();
+        assert_eq!(seeds, other_seeds);
+
+        // attempts run in batches as large as the thread pool, which mustn't change which attempt is used
+        // random spawns fail a lot of attempts, so the batches matter there
+        let mut settings = Settings::default();
+        settings.spawn_loc = Spawn::FullyRandom;
+        settings.generator.retries = 30;
+        let generate_with_threads = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| generate_seed(&graph, settings.clone(), &Vec::new(), Some(String::from("threads")), false)).unwrap().0
+        };
+        assert_eq!(generate_with_threads(1), generate_with_threads(4));
+    }
+
+    #[test]
+    fn starting_items() {
+        let mut settings = Settings::default();
+        settings.starting_items = vec![String::from("2|100"), String::from("3x1|0")];
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings);
+        assert!(seeds[0].starts_with("3|0|2|100|mute\n3|0|1|0|mute\n3|0|1|0|mute\n3|0|1|0|mute\n"));
+
+        // starting items are granted on spawn and taken out of the item pool, so they aren't placed again
+        let grants = |code: &str| seeds[0].lines()
+            .filter(|line| (line.contains("  // ") || line.ends_with("|mute")) && item_code(line) == code)
+            .collect::<Vec<_>>();
+        assert_eq!(grants("2|100"), vec!["3|0|2|100|mute"]);
+        let health_fragments = grants("1|0");
+        assert_eq!(health_fragments.iter().filter(|line| line.ends_with("|mute")).count(), 3);
+        assert_eq!(health_fragments.len(), 24);
+    }
+
+    #[test]
+    fn custom_goal() {
+        let mut settings = Settings::default();
+        settings.goalmodes.push(GoalMode::Custom(CustomGoal {
+            name: String::from("Spirit Trials"),
+            conditions: vec![String::from("44964|45951=2"), String::from("44964|25545=2"), String::from("WestHollow.FullyDrained")],
+            required: Some(2),
+        }));
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings.clone());
+        assert!(seeds[0].starts_with("Flags: Spirit Trials\n"));
+
+        if let Some(GoalMode::Custom(goal)) = settings.goalmodes.last_mut() {
+            goal.required = Some(4);
+        }
+        assert!(generate_seed(&graph, settings.clone(), &Vec::new(), None, false).is_err());
+
+        if let Some(GoalMode::Custom(goal)) = settings.goalmodes.last_mut() {
+            goal.required = None;
+            goal.conditions.push(String::from("Nowhere.Nothing"));
+        }
+        assert!(generate_seed(&graph, settings, &Vec::new(), None, false).is_err());
+    }
+
+    #[test]
+    fn world_settings() {
+        let mut settings = Settings::default();
+        settings.worlds = 2;
+        settings.world_settings = vec![WorldSettings::default(), WorldSettings {
+            difficulty: Some(String::from("unsafe")),
+            header_list: Some(vec![PathBuf::from("spawn_with_sword")]),
+            ..WorldSettings::default()
+        }];
+        let graph = test_logic(&settings);
+        let unsafe_graph = test_logic(&settings.for_world(1));
+
+        assert!(generate_seed(&graph, settings.clone(), &Vec::new(), None, false).is_err());
+
+        let (seeds, _, _) = generate_multiworld_seed(&[&graph, &unsafe_graph], settings, &Vec::new(), None, false).unwrap();
+        assert!(!seeds[0].starts_with("Flags: Spawn with Sword"));
+        assert!(seeds[1].starts_with("Flags: Spawn with Sword"));
+    }
+
+    #[test]
+    fn world_custom_items() {
+        let mut settings = Settings::default();
+        settings.worlds = 2;
+        for (world_index, name) in ["Red", "Blue"].iter().enumerate() {
+            let header = std::env::temp_dir().join(format!("seedgen_world_custom_items_{}.wotwrh", world_index));
+            std::fs::write(&header, format!("!!add 3x 8|9|2|int|+1\n!!name 8|9|2|int|+1 *{} Fragment*\n", name)).unwrap();
+            settings.world_settings.push(WorldSettings { header_list: Some(vec![header]), ..WorldSettings::default() });
+        }
+        let graph = test_logic(&settings);
+
+        let (seeds, _, _) = generate_multiworld_seed(&[&graph, &graph], settings, &Vec::new(), None, false).unwrap();
+        for (seed, name) in seeds.iter().zip(["*Red Fragment*", "*Blue Fragment*"]) {
+            let fragments = seed.lines().filter(|line| line.contains("|8|9|2|int|+1 ")).collect::<Vec<_>>();
+            assert_eq!(fragments.len(), 3);
+            assert!(fragments.iter().all(|line| line.contains(name)));
+        }
+    }
+
+    #[test]
+    fn placement_constraints() {
+        let mut settings = Settings::default();
+        settings.worlds = 2;
+        settings.constraints = vec![
+            PlacementConstraint { item: String::from("2|0"), constraint: Constraint::InZone(String::from("Marsh")) },
+            PlacementConstraint { item: String::from("2|5"), constraint: Constraint::NotInShop },
+            PlacementConstraint { item: String::from("2|8"), constraint: Constraint::InWorld(2) },
+        ];
+        let graph = test_logic(&settings);
+
+        let seeds = test_seeds(&graph, settings);
+
+        for (world_index, seed) in seeds.iter().enumerate() {
+            // skip the items received from other worlds, the sending world holds the location
+            for line in seed.lines().filter(|line| line.contains("  // ") && !line.starts_with("12|")) {
+                let item_code = item_code(line);
+                let holds = |code: &str| item_code == code || line.contains(&format!("$[{}]", code));
+
+                if holds("2|0") { assert!(line.ends_with("Marsh"), "Bash placed outside of Marsh: {}", line); }
+                if holds("2|5") { assert!(!line.ends_with("Shop"), "Double Jump placed in a shop: {}", line); }
+                if holds("2|8") { assert_eq!(world_index, 1, "Launch placed outside of world 2: {}", line); }
+            }
+        }
+
+        // worlds are counted from 1
+        for world in [0, 3] {
+            let mut settings = Settings::default();
+            settings.worlds = 2;
+            settings.constraints = vec![PlacementConstraint { item: String::from("2|8"), constraint: Constraint::InWorld(world) }];
+            assert!(generator::parse_constraints(&graph, &settings).is_err());
+        }
     }
 }
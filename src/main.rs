@@ -1,7 +1,7 @@
 use std::{
     fs,
     str::FromStr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     convert::TryFrom,
     io::{self, Read},
     time::Instant,
@@ -19,10 +19,10 @@ use seedgen::{self, item, world, settings, util, languages::{headers::{self, par
 use item::{Item, Resource, Skill, Shard, Teleporter};
 use world::{
     World,
-    graph::Graph,
+    graph::{Graph, ExportFormat, BlockedConnection, RouteStep},
 };
-use settings::{Settings, Spawn};
-use util::{Difficulty, Glitch, GoalMode, UberState};
+use settings::{Settings, Spawn, PlacementConstraint, Constraint, GeneratorConfig, WorldSettings};
+use util::{Glitch, GoalMode, CustomGoal, UberState, Zone};
 
 #[derive(StructOpt, Debug)]
 /// Generate seeds for the Ori 2 randomizer.
@@ -63,6 +63,11 @@ enum SeedGenCommand {
         #[structopt(subcommand)]
         subcommand: Option<HeaderCommand>,
     },
+    /// Analyze the logic files
+    Logic {
+        #[structopt(subcommand)]
+        command: LogicCommand,
+    },
 }
 
 #[derive(StructOpt, Debug)]
@@ -100,6 +105,9 @@ struct SeedArgs {
     /// launch the seed after generating
     #[structopt(short, long)]
     launch: bool,
+    /// additionally write a machine-readable spoiler in json format
+    #[structopt(long)]
+    json_spoiler: bool,
     #[structopt(flatten)]
     settings: SeedSettings,
     /// inline headers
@@ -133,7 +141,7 @@ struct SeedSettings {
     names: Vec<String>,
     /// difficulty of execution you may be required to perform
     ///
-    /// one of moki, gorlek, unsafe
+    /// one of moki, gorlek, kii, unsafe or a difficulty declared in the areas file
     #[structopt(short, long, default_value = "moki")]
     difficulty: String,
     /// glitches you may be required to use
@@ -144,11 +152,14 @@ struct SeedSettings {
     /// which goal modes to use
     ///
     /// goal modes are trees, wisps, quests, relics. Relics can further configure the chance per area to have a relic, default is relics:60%
+    /// Custom goals are given as custom:<name>:<conditions>[:<required amount>], where conditions is a comma-separated list of logic states, pickups or uber states,
+    /// e.g. "custom:Drain Everything:WestHollow.FullyDrained,InnerWellspring.DrainLever" or "custom:Spirit Trials:44964|45951=2,44964|25545=2,44964|11512=2:2"
     #[structopt(short, long)]
     goals: Vec<String>,
     /// where to spawn the player
     ///
     /// Use an anchor name from the areas file, "r" / "random" for a random teleporter or "f" / "fullyrandom" for any location
+    /// or a comma-separated pool of anchors to choose from, each optionally followed by a weight, e.g. "MarshSpawn.Main:3,HowlsDen.Teleporter,GladesTown.Teleporter:0.5"
     #[structopt(short, long, default_value = "MarshSpawn.Main")]
     spawn: String,
     /// hides spoilers
@@ -171,6 +182,43 @@ struct SeedSettings {
     /// format for one parameter: <headername>.<parametername>=<value>
     #[structopt(short = "a", long = "args")]
     header_args: Vec<String>,
+    /// locations that should never hold progression
+    ///
+    /// either the name of a pickup location from the areas file or a zone to exclude all of its locations, e.g. "MarshSpawn.RockHC" or "Wastes"
+    #[structopt(long)]
+    exclude: Vec<String>,
+    /// restrict where an item may be placed
+    ///
+    /// format for one constraint: <item>:<constraint>, where the item is given in the same format as in headers and the constraint is one of zone=<zone>, noshop or world=<world number>
+    /// e.g. "2|8:zone=Marsh" or "1|2:noshop"
+    #[structopt(short = "c", long = "constraint")]
+    constraints: Vec<String>,
+    /// the maximum amount of Spirit Light all shop items together may cost
+    #[structopt(long)]
+    shop_budget: Option<u32>,
+    /// items to grant on spawn, which are taken out of the item pool
+    ///
+    /// items are given in the same format as in headers, optionally with an amount in front, e.g. "2|100" for Sword or "3x1|0" for three Health Fragments
+    #[structopt(long = "start")]
+    starting_items: Vec<String>,
+    /// shuffle where each teleporter leads to
+    #[structopt(long)]
+    random_teleporters: bool,
+    /// shuffle the targets of the connections marked as doors in the logic file
+    #[structopt(long)]
+    random_doors: bool,
+    /// randomize how many keystones each keystone door costs, the keystone pool grows or shrinks accordingly
+    ///
+    /// format: <min>-<max>, e.g. "1-4"
+    #[structopt(long)]
+    keystone_costs: Option<String>,
+    /// override settings for one world of a multiworld
+    ///
+    /// format for one override: <world number>:<setting>=<value>, where the setting is one of difficulty, glitches (comma-separated), spawn, header or arg
+    /// header and arg can be given multiple times and replace the headers or header args of that world
+    /// e.g. "2:difficulty=unsafe" or "1:header=spawn_with_sword"
+    #[structopt(short = "W", long = "world")]
+    world_settings: Vec<String>,
 }
 
 #[derive(StructOpt, Debug)]
@@ -187,6 +235,12 @@ struct ReachCheckArgs {
     /// the input file representing state namings
     #[structopt(parse(from_os_str), default_value = "state_data.csv", short, long)]
     uber_states: PathBuf,
+    #[structopt(flatten)]
+    inventory: InventoryArgs,
+}
+
+#[derive(StructOpt, Debug)]
+struct InventoryArgs {
     /// player health (one orb is 10 health)
     health: u16,
     /// player energy (one orb is 1 energy)
@@ -217,6 +271,105 @@ enum HeaderCommand {
     }
 }
 
+#[derive(StructOpt, Debug)]
+struct LogicFiles {
+    /// the input file representing the logic
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", short, long)]
+    areas: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", short, long)]
+    locations: PathBuf,
+    /// the input file representing state namings
+    #[structopt(parse(from_os_str), default_value = "state_data.csv", short, long)]
+    uber_states: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct LogicArgs {
+    #[structopt(flatten)]
+    files: LogicFiles,
+    /// difficulty of execution to assume, see seed --help for the available difficulties
+    #[structopt(short, long, default_value = "moki")]
+    difficulty: String,
+    /// glitches to assume, see seed --help for the available glitches
+    #[structopt(short = "G", long)]
+    glitches: Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+enum LogicCommand {
+    /// Find anchors which can be reached, but not left again towards the spawn or an unlocked teleporter
+    Traps {
+        #[structopt(flatten)]
+        args: LogicArgs,
+        /// spawns to check, or leave empty to check every anchor that can be spawned on
+        #[structopt(short, long, number_of_values = 1)]
+        spawn: Vec<String>,
+        /// player items in the format s:<skill id>, t:<teleporter id>, sh:<shard id>, w:<world event id> or u:<ubergroup>,<uberid>
+        items: Vec<String>,
+    },
+    /// Export the logic as a graph to visualize it
+    Export {
+        #[structopt(flatten)]
+        args: LogicArgs,
+        /// output format, one of dot, graphml
+        #[structopt(short, long, default_value = "dot")]
+        format: String,
+        /// only export the logic of these zones, e.g. marsh, glades
+        #[structopt(short, long, number_of_values = 1)]
+        zone: Vec<String>,
+    },
+    /// Explain why a node can't be reached with the given items
+    Explain {
+        #[structopt(flatten)]
+        args: LogicArgs,
+        /// where to start from
+        #[structopt(short, long, default_value = "MarshSpawn.Main")]
+        spawn: String,
+        /// how many of the cheapest itemsets to show for each blocked connection
+        #[structopt(short = "n", long, default_value = "3")]
+        max_unlocks: usize,
+        /// the anchor, pickup or state in question
+        target: String,
+        #[structopt(flatten)]
+        inventory: InventoryArgs,
+    },
+    /// Find the shortest route to a node with the given items
+    Route {
+        #[structopt(flatten)]
+        args: LogicArgs,
+        /// the anchor to start from
+        start: String,
+        /// the anchor, pickup or state to go to
+        target: String,
+        #[structopt(flatten)]
+        inventory: InventoryArgs,
+    },
+    /// Check the logic for likely mistakes
+    Lint {
+        #[structopt(flatten)]
+        files: LogicFiles,
+        /// print the issues as json
+        #[structopt(long)]
+        json: bool,
+    },
+    /// Compare two versions of the logic on every difficulty
+    Diff {
+        /// the previous version of the logic
+        #[structopt(parse(from_os_str))]
+        old: PathBuf,
+        /// the new version of the logic
+        #[structopt(parse(from_os_str))]
+        new: PathBuf,
+        /// the input file representing pickup locations
+        #[structopt(parse(from_os_str), default_value = "loc_data.csv", short, long)]
+        locations: PathBuf,
+        /// the input file representing state namings
+        #[structopt(parse(from_os_str), default_value = "state_data.csv", short, long)]
+        uber_states: PathBuf,
+    },
+}
+
 fn read_header() -> String {
     // If we do not have input, skip.
     if atty::is(atty::Stream::Stdin) {
@@ -240,16 +393,6 @@ fn read_header() -> String {
     output
 }
 
-fn parse_difficulty(difficulty: &str) -> Result<Difficulty, String> {
-    match &difficulty.to_lowercase()[..] {
-        "moki" => Ok(Difficulty::Moki),
-        "gorlek" => Ok(Difficulty::Gorlek),
-        "kii" => Ok(Difficulty::Kii),
-        "unsafe" => Ok(Difficulty::Unsafe),
-        _ => Err(format!("Unknown difficulty {}", difficulty)),
-    }
-}
-
 fn parse_glitches(names: &[String]) -> Vec<Glitch> {
     let mut glitches = Vec::default();
 
@@ -309,6 +452,16 @@ fn parse_goalmodes(names: &[String]) -> Result<Vec<GoalMode>, String> {
 
                 goalmodes.push(goal);
             },
+            "c" | "custom" => {
+                let name = parts.next().ok_or_else(|| format!("expected name in goal mode {}", goalmode))?.to_string();
+                let conditions = parts.next().ok_or_else(|| format!("expected conditions in goal mode {}", goalmode))?
+                    .split(',')
+                    .map(|condition| condition.trim().to_string())
+                    .collect();
+                let required = parts.next().map(|required| required.parse().map_err(|_| format!("Invalid amount in details string for goal mode {}", goalmode))).transpose()?;
+
+                goalmodes.push(GoalMode::Custom(CustomGoal { name, conditions, required }));
+            },
             other => log::warn!("Unknown goal mode {}", other),
         }
 
@@ -333,13 +486,74 @@ fn parse_goalmodes(names: &[String]) -> Result<Vec<GoalMode>, String> {
 
     Ok(goalmodes)
 }
-fn parse_spawn(spawn: String) -> Spawn {
+fn parse_world_settings(overrides: &[String]) -> Result<Vec<WorldSettings>, String> {
+    let mut world_settings = Vec::<WorldSettings>::new();
+
+    for world_override in overrides {
+        let (world, setting) = world_override.split_once(':').ok_or_else(|| format!("expected <world>:<setting>=<value> in world setting {}", world_override))?;
+        let world: usize = world.parse().map_err(|_| format!("Invalid world number in world setting {}", world_override))?;
+        if world == 0 { return Err(format!("World numbers start at 1 in world setting {}", world_override)); }
+        let (setting, value) = setting.split_once('=').ok_or_else(|| format!("expected <world>:<setting>=<value> in world setting {}", world_override))?;
+
+        if world_settings.len() < world {
+            world_settings.resize_with(world, WorldSettings::default);
+        }
+        let overrides = &mut world_settings[world - 1];
+
+        match setting {
+            "difficulty" => overrides.difficulty = Some(value.to_string()),
+            "glitches" => overrides.glitches = Some(parse_glitches(&value.split(',').map(str::to_string).collect::<Vec<_>>())),
+            "spawn" => overrides.spawn_loc = Some(parse_spawn(value.to_string())?),
+            "header" => overrides.header_list.get_or_insert_with(Vec::new).push(PathBuf::from(value)),
+            "arg" => overrides.header_args.get_or_insert_with(Vec::new).push(value.to_string()),
+            _ => return Err(format!("Unknown setting {} in world setting {}", setting, world_override)),
+        }
+    }
+
+    Ok(world_settings)
+}
+
+fn parse_spawn(spawn: String) -> Result<Spawn, String> {
     match &spawn.to_lowercase()[..] {
-        "r" | "random" => Spawn::Random,
-        "f" | "fullyrandom" => Spawn::FullyRandom,
-        _ => Spawn::Set(spawn),
+        "r" | "random" => Ok(Spawn::Random),
+        "f" | "fullyrandom" => Ok(Spawn::FullyRandom),
+        _ if spawn.contains(',') || spawn.contains(':') => spawn.split(',').map(|spawn_loc| {
+            let (spawn_loc, weight) = match spawn_loc.split_once(':') {
+                Some((spawn_loc, weight)) => (spawn_loc, weight.trim().parse().map_err(|_| format!("Invalid weight {} for {} in spawn pool", weight, spawn_loc))?),
+                None => (spawn_loc, 1.0),
+            };
+            Ok((spawn_loc.trim().to_string(), weight))
+        }).collect::<Result<_, String>>().map(Spawn::Pool),
+        _ => Ok(Spawn::Set(spawn)),
     }
 }
+fn parse_constraints(constraints: &[String]) -> Result<Vec<PlacementConstraint>, String> {
+    constraints.iter().map(|constraint| {
+        let mut parts = constraint.rsplitn(2, ':');
+        let details = parts.next().unwrap();
+        let item = parts.next().ok_or_else(|| format!("expected <item>:<constraint> in placement constraint {}", constraint))?.to_string();
+
+        let constraint = match details.split_once('=') {
+            Some(("zone", zone)) => Constraint::InZone(zone.to_string()),
+            Some(("world", world)) => {
+                let world = world.parse().map_err(|_| format!("Invalid world number in placement constraint {}", constraint))?;
+                if world == 0 { return Err(format!("World numbers start at 1 in placement constraint {}", constraint)); }
+                Constraint::InWorld(world)
+            },
+            None if details == "noshop" => Constraint::NotInShop,
+            _ => return Err(format!("Unknown constraint {} in placement constraint {}", details, constraint)),
+        };
+
+        Ok(PlacementConstraint { item, constraint })
+    }).collect()
+}
+fn parse_keystone_costs(keystone_costs: &str) -> Result<(u16, u16), String> {
+    let (min, max) = keystone_costs.split_once('-').ok_or_else(|| format!("Expected <min>-<max> in keystone costs {}", keystone_costs))?;
+    let min = min.trim().parse().map_err(|_| format!("Invalid minimum in keystone costs {}", keystone_costs))?;
+    let max = max.trim().parse().map_err(|_| format!("Invalid maximum in keystone costs {}", keystone_costs))?;
+    if min > max { return Err(format!("Minimum is greater than maximum in keystone costs {}", keystone_costs)); }
+    Ok((min, max))
+}
 fn parse_settings(settings: SeedSettings) -> Result<Settings, String> {
     let SeedSettings {
         preset,
@@ -355,12 +569,22 @@ fn parse_settings(settings: SeedSettings) -> Result<Settings, String> {
         goals,
         header_paths,
         header_args,
+        exclude,
+        constraints,
+        shop_budget,
+        starting_items,
+        random_teleporters,
+        random_doors,
+        keystone_costs,
+        world_settings,
     } = settings;
 
-    let difficulty = parse_difficulty(&difficulty)?;
     let glitches = parse_glitches(&glitches);
     let goalmodes = parse_goalmodes(&goals)?;
-    let spawn = parse_spawn(spawn);
+    let spawn = parse_spawn(spawn)?;
+    let constraints = parse_constraints(&constraints)?;
+    let world_settings = parse_world_settings(&world_settings)?;
+    let keystone_costs = keystone_costs.map(|keystone_costs| parse_keystone_costs(&keystone_costs)).transpose()?;
 
     if worlds == 0 {
         return Err(String::from("Tried to create a seed with zero worlds"));
@@ -383,10 +607,19 @@ fn parse_settings(settings: SeedSettings) -> Result<Settings, String> {
         hard,
         header_list: header_paths,
         header_args,
+        exclude,
+        constraints,
+        shop_budget,
+        starting_items,
+        randomize_teleporters: random_teleporters,
+        randomize_doors: random_doors,
+        keystone_costs,
+        generator: GeneratorConfig::default(),
+        world_settings,
     })
 }
 
-fn write_seeds_to_files(seeds: &[String], spoilers: &[String], mut filename: String, mut folder: PathBuf, players: &[String], race: bool) -> Result<(), String> {
+fn write_seeds_to_files(seeds: &[String], spoilers: &[String], json_spoilers: &[String], mut filename: String, mut folder: PathBuf, players: &[String], race: bool) -> Result<(), String> {
     let seed_count = seeds.len();
     let multiworld = seed_count > 1;
 
@@ -421,6 +654,14 @@ fn write_seeds_to_files(seeds: &[String], spoilers: &[String], mut filename: Str
             log::info!("Wrote spoiler for {} to {}", player, file.display());
         }
 
+        if let Some(json_spoiler) = json_spoilers.get(index) {
+            let json_spoiler_filename = format!("{}.spoiler.json", file.file_stem().unwrap().to_string_lossy());
+            path.set_file_name(json_spoiler_filename);
+
+            let file = util::create_file(&path, json_spoiler, "", true)?;
+            log::info!("Wrote json spoiler for {} to {}", player, file.display());
+        }
+
         if first {
             first = false;
             if let Some(path) = file.to_str() {
@@ -448,7 +689,21 @@ fn generate_seeds(mut args: SeedArgs) -> Result<(), String> {
 
     let settings = parse_settings(args.settings)?.apply_presets()?;
 
-    let graph = languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, !args.trust)?;
+    // worlds with the same difficulty and glitches can share their logic
+    let mut graphs = Vec::new();
+    let mut world_graphs = Vec::with_capacity(settings.worlds);
+    for world_index in 0..settings.worlds {
+        let world_settings = settings.for_world(world_index);
+        let graph_index = if let Some(graph_index) = graphs.iter().position(|(difficulty, glitches, _)| difficulty == &world_settings.difficulty && glitches == &world_settings.glitches) {
+            graph_index
+        } else {
+            let graph = languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &world_settings, !args.trust)?;
+            graphs.push((world_settings.difficulty, world_settings.glitches, graph));
+            graphs.len() - 1
+        };
+        world_graphs.push(graph_index);
+    }
+    let world_graphs = world_graphs.into_iter().map(|graph_index| &graphs[graph_index].2).collect::<Vec<_>>();
     log::info!("Parsed logic in {:?}", now.elapsed());
 
     let header = read_header();
@@ -459,7 +714,7 @@ fn generate_seeds(mut args: SeedArgs) -> Result<(), String> {
     let worlds = settings.worlds;
     let race = settings.race;
     let players = settings.players.clone();
-    let (seeds, spoilers) = seedgen::generate_seed(&graph, settings, &args.inline_headers, seed).map_err(|err| format!("Error generating seed: {}", err))?;
+    let (seeds, spoilers, json_spoilers) = seedgen::generate_multiworld_seed(&world_graphs, settings, &args.inline_headers, seed, args.json_spoiler).map_err(|err| format!("Error generating seed: {}", err))?;
     if worlds == 1 {
         log::info!("Generated seed in {:?}", now.elapsed());
     } else {
@@ -472,10 +727,14 @@ fn generate_seeds(mut args: SeedArgs) -> Result<(), String> {
             println!("\n======= SPOILERS =======\n");
             write_seeds_to_stdout(spoilers);
         }
+        if args.json_spoiler {
+            println!("\n======= JSON SPOILERS =======\n");
+            write_seeds_to_stdout(json_spoilers);
+        }
     } else {
         let filename = args.filename.unwrap_or_else(|| String::from("seed"));
 
-        write_seeds_to_files(&seeds, &spoilers, filename, args.seed_folder, &players, race).unwrap_or_else(|err| log::error!("{}", err));
+        write_seeds_to_files(&seeds, &spoilers, &json_spoilers, filename, args.seed_folder, &players, race).unwrap_or_else(|err| log::error!("{}", err));
     }
 
     if args.launch {
@@ -508,19 +767,7 @@ fn create_preset(mut args: PresetArgs) -> Result<(), String> {
     Ok(())
 }
 
-fn reach_check(mut args: ReachCheckArgs) -> Result<String, String> {
-    let command = env::args().collect::<Vec<_>>().join(" ");
-    log::trace!("{}", command);
-
-    args.seed_file.set_extension("wotwr");
-    let contents = util::read_file(&args.seed_file, "seeds")?;
-
-    let settings = Settings::from_seed(&contents)?;
-    let graph = &languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, false)?;
-    let mut world = World::new(graph);
-
-    world.player.apply_settings(&settings);
-
+fn grant_inventory(world: &mut World, args: InventoryArgs) -> Result<(), String> {
     world.player.inventory.grant(Item::Resource(Resource::Health), args.health / 5);
     #[allow(clippy::cast_possible_truncation)]
     world.player.inventory.grant(Item::Resource(Resource::Energy), util::float_to_int(args.energy * 2.0).map_err(|_| format!("Invalid energy parameter {}", args.energy))?);
@@ -528,7 +775,11 @@ fn reach_check(mut args: ReachCheckArgs) -> Result<String, String> {
     world.player.inventory.grant(Item::Resource(Resource::Ore), args.ore);
     world.player.inventory.grant(Item::SpiritLight(1), u16::try_from(args.spirit_light).unwrap_or(u16::MAX));  // Higher amounts of Spirit Light are irrelevant, just want to accept high values in case the player has that much);
 
-    for item in args.items {
+    grant_items(world, args.items)
+}
+
+fn grant_items(world: &mut World, items: Vec<String>) -> Result<(), String> {
+    for item in items {
         if let Some(skill) = item.strip_prefix("s:") {
             let id: u8 = skill.parse().map_err(|_| format!("expected numeric skill id in {}", item))?;
             world.player.inventory.grant(Item::Skill(Skill::try_from(id).map_err(|_| format!("{} is not a valid skill id", id))?), 1);
@@ -556,6 +807,24 @@ fn reach_check(mut args: ReachCheckArgs) -> Result<String, String> {
         }
     }
 
+    Ok(())
+}
+
+fn reach_check(mut args: ReachCheckArgs) -> Result<String, String> {
+    let command = env::args().collect::<Vec<_>>().join(" ");
+    log::trace!("{}", command);
+
+    args.seed_file.set_extension("wotwr");
+    let contents = util::read_file(&args.seed_file, "seeds")?;
+
+    let settings = Settings::from_seed(&contents)?;
+    let graph = &languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, false)?;
+    let mut world = World::new(graph);
+
+    world.player.apply_settings(&settings);
+
+    grant_inventory(&mut world, args.inventory)?;
+
     for line in contents.lines() {
         if let Some(sets) = line.strip_prefix("// Sets: ") {
             if !sets.is_empty() {
@@ -591,6 +860,143 @@ fn reach_check(mut args: ReachCheckArgs) -> Result<String, String> {
     Ok(reached)
 }
 
+fn parse_logic_args(args: &LogicArgs) -> Result<(Graph, Settings), String> {
+    let mut settings = Settings::default();
+    settings.difficulty = args.difficulty.clone();
+    settings.glitches = parse_glitches(&args.glitches);
+
+    let graph = languages::parse_logic(&args.files.areas, &args.files.locations, &args.files.uber_states, &settings, false)?;
+    Ok((graph, settings))
+}
+
+fn logic_traps(args: &LogicArgs, spawns: &[String], items: Vec<String>) -> Result<(), String> {
+    let (graph, settings) = parse_logic_args(args)?;
+    let mut world = World::new(&graph);
+    world.player.apply_settings(&settings);
+    grant_items(&mut world, items)?;
+
+    let spawns = if spawns.is_empty() {
+        graph.nodes.iter().filter(|node| node.can_spawn()).collect::<Vec<_>>()
+    } else {
+        spawns.iter().map(|spawn| graph.find_spawn(spawn)).collect::<Result<Vec<_>, String>>()?
+    };
+
+    let mut trapped = 0;
+    for spawn in &spawns {
+        let traps = graph.traps(&world.player, spawn, &world.uber_states, &world.sets)?;
+        if !traps.is_empty() {
+            trapped += 1;
+            let identifiers = traps.iter().map(|node| node.identifier()).collect::<Vec<_>>().join(", ");
+            println!("{}: {}", spawn.identifier(), identifiers);
+        }
+    }
+    log::info!("Found traps for {} out of {} spawns", trapped, spawns.len());
+
+    Ok(())
+}
+
+const ALL_ZONES: &[Zone] = &[
+    Zone::Marsh, Zone::Hollow, Zone::Glades, Zone::Wellspring, Zone::Pools, Zone::Burrows, Zone::Reach, Zone::Woods,
+    Zone::Depths, Zone::Wastes, Zone::Ruins, Zone::Willow, Zone::Shop, Zone::Void, Zone::Spawn,
+];
+
+fn logic_export(args: &LogicArgs, format: &str, zones: &[String]) -> Result<(), String> {
+    let format = match &format.to_lowercase()[..] {
+        "dot" => ExportFormat::Dot,
+        "graphml" => ExportFormat::GraphML,
+        _ => return Err(format!("Unknown export format {}", format)),
+    };
+    let zones = zones.iter().map(|name| {
+        ALL_ZONES.iter().copied().find(|zone| zone.to_string().eq_ignore_ascii_case(name)).ok_or_else(|| format!("Unknown zone {}", name))
+    }).collect::<Result<Vec<_>, String>>()?;
+
+    let (graph, _) = parse_logic_args(args)?;
+    print!("{}", graph.export(format, &zones));
+
+    Ok(())
+}
+
+fn logic_explain(args: &LogicArgs, spawn: &str, max_unlocks: usize, target: &str, inventory: InventoryArgs) -> Result<(), String> {
+    let (graph, settings) = parse_logic_args(args)?;
+    let mut world = World::new(&graph);
+    world.player.apply_settings(&settings);
+    grant_inventory(&mut world, inventory)?;
+
+    let spawn = graph.find_spawn(spawn)?;
+    let target = graph.nodes.iter().find(|node| node.identifier() == target).ok_or_else(|| format!("{} not found", target))?;
+
+    match graph.explain_unreachable(&world.player, spawn, target, &world.uber_states, &world.sets, max_unlocks) {
+        None => println!("{} can be reached", target),
+        Some(blocked) => {
+            if blocked.is_empty() {
+                println!("{} can't be reached and no connection leads towards it", target);
+            }
+            for BlockedConnection { from, to, requirement, unlocks } in blocked {
+                println!("{} -> {}: {}", from, to, graph.display_requirement(requirement));
+                if unlocks.is_empty() {
+                    println!("  needs progress elsewhere first");
+                }
+                for unlock in unlocks {
+                    println!("  {}", unlock);
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn logic_route(args: &LogicArgs, start: &str, target: &str, inventory: InventoryArgs) -> Result<(), String> {
+    let (graph, settings) = parse_logic_args(args)?;
+    let mut world = World::new(&graph);
+    world.player.apply_settings(&settings);
+    grant_inventory(&mut world, inventory)?;
+
+    let start = graph.find_spawn(start)?;
+    let target = graph.nodes.iter().find(|node| node.identifier() == target).ok_or_else(|| format!("{} not found", target))?;
+
+    let route = graph.find_route(&world.player, start, target, &world.uber_states, &world.sets).ok_or_else(|| format!("{} can't be reached from {}", target, start))?;
+    for RouteStep { node, requirement, orbs } in route {
+        let orbs = orbs.iter().map(|orbs| format!("{} health, {} energy", orbs.health, orbs.energy)).collect::<Vec<_>>().join(" or ");
+        match requirement {
+            Some(requirement) => println!("{}: {}  ({})", node, graph.display_requirement(requirement), orbs),
+            None => println!("{}  ({})", node, orbs),
+        }
+    }
+
+    Ok(())
+}
+
+fn logic_lint(files: &LogicFiles, json: bool) -> Result<(), String> {
+    let issues = languages::logic::lint_logic(&files.areas, &files.locations, &files.uber_states)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&issues).map_err(|err| format!("Failed to serialize the issues: {}", err))?);
+    } else {
+        for issue in &issues {
+            println!("{}", issue);
+        }
+    }
+
+    if issues.is_empty() {
+        log::info!("No issues found");
+        Ok(())
+    } else {
+        Err(format!("Found {} issues", issues.len()))
+    }
+}
+
+fn logic_diff(old: &Path, new: &Path, locations: &Path, uber_states: &Path) -> Result<(), String> {
+    let changes = languages::logic::diff::diff_logic(old, new, locations, uber_states)?;
+
+    for change in &changes {
+        println!("{}", change);
+    }
+    log::info!("Found {} changes", changes.len());
+
+    Ok(())
+}
+
 fn compile_seed(mut path: PathBuf) -> Result<(), String> {
     if path.extension().is_none() {
         path.set_extension("wotwrh");
@@ -672,5 +1078,21 @@ fn main() {
                 Err(err) => log::error!("{}", err),
             }
         },
+        SeedGenCommand::Logic { command } => {
+            seedgen::initialize_log(None, LevelFilter::Info, false).unwrap_or_else(|err| eprintln!("Failed to initialize log: {}", err));
+
+            let result = match command {
+                LogicCommand::Traps { args, spawn, items } => logic_traps(&args, &spawn, items),
+                LogicCommand::Export { args, format, zone } => logic_export(&args, &format, &zone),
+                LogicCommand::Explain { args, spawn, max_unlocks, target, inventory } => logic_explain(&args, &spawn, max_unlocks, &target, inventory),
+                LogicCommand::Route { args, start, target, inventory } => logic_route(&args, &start, &target, inventory),
+                LogicCommand::Lint { files, json } => logic_lint(&files, json),
+                LogicCommand::Diff { old, new, locations, uber_states } => logic_diff(&old, &new, &locations, &uber_states),
+            };
+            result.unwrap_or_else(|err| {
+                log::error!("{}", err);
+                process::exit(2);
+            });
+        },
     }
 }
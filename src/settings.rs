@@ -20,6 +20,7 @@ pub enum Spawn {
     Set(String),
     Random,
     FullyRandom,
+    Pool(Vec<(String, f32)>),  // anchors to choose from, with their relative weights
 }
 impl Default for Spawn {
     fn default() -> Spawn {
@@ -27,6 +28,127 @@ impl Default for Spawn {
     }
 }
 
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum Constraint {
+    InZone(String),
+    NotInShop,
+    InWorld(usize),
+}
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct PlacementConstraint {
+    pub item: String,
+    pub constraint: Constraint,
+}
+
+/// Settings which can differ between the worlds of a multiworld, anything left unset falls back to the shared settings
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorldSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glitches: Option<Vec<Glitch>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spawn_loc: Option<Spawn>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_list: Option<Vec<PathBuf>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header_args: Option<Vec<String>>,
+}
+impl WorldSettings {
+    fn merge(&mut self, other: WorldSettings) {
+        let WorldSettings {
+            difficulty,
+            glitches,
+            spawn_loc,
+            header_list,
+            header_args,
+        } = other;
+
+        override_with(&mut self.difficulty, difficulty.map(Some));
+        override_with(&mut self.glitches, glitches.map(Some));
+        override_with(&mut self.spawn_loc, spawn_loc.map(Some));
+        override_with(&mut self.header_list, header_list.map(Some));
+        override_with(&mut self.header_args, header_args.map(Some));
+    }
+}
+
+// settings that are left unset keep the value they override
+fn override_with<T>(value: &mut T, other: Option<T>) {
+    if let Some(other) = other { *value = other; }
+}
+
+/// Tuning for the placement algorithm
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GeneratorConfig {
+    pub retries: u16,  // How many retries to allow when generating a seed
+    pub placeholder_slots: usize,  // how many slots to keep as placeholders for bigger progressions
+    pub reserve_slots: usize,  // how many slots to reserve after random placements for the next iteration
+    pub random_progression: f64,  // How likely to choose a progression item as random placement
+    pub price_range: (f32, f32),  // range of the random modifier applied to shop prices
+    pub spirit_light_range: (f32, f32),  // range of the random modifier applied to the base amount of Spirit Light pickups
+    pub progression_balancing: f32,  // how strongly to favour multiworld players whose worlds went through fewer spheres than the others
+    pub avoid_softlocks: bool,  // whether to avoid progressions which open up areas that can't be left again
+}
+impl Default for GeneratorConfig {
+    fn default() -> GeneratorConfig {
+        GeneratorConfig {
+            retries: 10,
+            placeholder_slots: 25,
+            reserve_slots: 1,
+            random_progression: 0.4,
+            price_range: (0.75, 1.25),
+            spirit_light_range: (0.75, 1.25),
+            progression_balancing: 0.0,
+            avoid_softlocks: true,
+        }
+    }
+}
+impl GeneratorConfig {
+    // only the values which differ from the default are taken over, so a preset tuning one of them leaves the others alone
+    fn merge(&mut self, other: GeneratorConfig) {
+        let default = GeneratorConfig::default();
+        let GeneratorConfig {
+            retries,
+            placeholder_slots,
+            reserve_slots,
+            random_progression,
+            price_range,
+            spirit_light_range,
+            progression_balancing,
+            avoid_softlocks,
+        } = other;
+
+        if retries != default.retries { self.retries = retries; }
+        if placeholder_slots != default.placeholder_slots { self.placeholder_slots = placeholder_slots; }
+        if reserve_slots != default.reserve_slots { self.reserve_slots = reserve_slots; }
+        if random_progression != default.random_progression { self.random_progression = random_progression; }
+        if price_range != default.price_range { self.price_range = price_range; }
+        if spirit_light_range != default.spirit_light_range { self.spirit_light_range = spirit_light_range; }
+        if progression_balancing != default.progression_balancing { self.progression_balancing = progression_balancing; }
+        if avoid_softlocks != default.avoid_softlocks { self.avoid_softlocks = avoid_softlocks; }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.retries == 0 {
+            return Err(String::from("The generator needs to be allowed at least one try"));
+        }
+        if !(0.0..=1.0).contains(&self.random_progression) {
+            return Err(format!("Invalid random progression chance {}, needs to be between 0 and 1", self.random_progression));
+        }
+        if !(0.0..=1.0).contains(&self.progression_balancing) {
+            return Err(format!("Invalid progression balancing {}, needs to be between 0 and 1", self.progression_balancing));
+        }
+        for (name, (low, high)) in &[("price", self.price_range), ("Spirit Light", self.spirit_light_range)] {
+            if !(*low >= 0.0 && low <= high && high.is_finite()) {
+                return Err(format!("Invalid {} range {}-{}", name, low, high));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Settings {
@@ -35,7 +157,8 @@ pub struct Settings {
     pub presets: Vec<PathBuf>,
     pub worlds: usize,
     pub players: Vec<String>,
-    pub difficulty: Difficulty,
+    /// The difficulty tier for the logic, either a builtin one or one declared in the areas file
+    pub difficulty: String,
     pub glitches: Vec<Glitch>,
     pub goalmodes: Vec<GoalMode>,
     pub spawn_loc: Spawn,
@@ -45,6 +168,24 @@ pub struct Settings {
     pub hard: bool,
     pub header_list: Vec<PathBuf>,
     pub header_args: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub constraints: Vec<PlacementConstraint>,
+    #[serde(default)]
+    pub shop_budget: Option<u32>,
+    #[serde(default)]
+    pub starting_items: Vec<String>,
+    #[serde(default)]
+    pub randomize_teleporters: bool,
+    #[serde(default)]
+    pub randomize_doors: bool,
+    #[serde(default)]
+    pub keystone_costs: Option<(u16, u16)>,
+    #[serde(default)]
+    pub generator: GeneratorConfig,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub world_settings: Vec<WorldSettings>,
 }
 impl Default for Settings {
     fn default() -> Settings {
@@ -53,7 +194,7 @@ impl Default for Settings {
             presets: Vec::default(),
             worlds: 1,
             players: Vec::default(),
-            difficulty: Difficulty::default(),
+            difficulty: Difficulty::default().name().to_string(),
             glitches: Vec::default(),
             goalmodes: Vec::default(),
             spawn_loc: Spawn::default(),
@@ -63,6 +204,15 @@ impl Default for Settings {
             hard: false,
             header_list: Vec::default(),
             header_args: Vec::default(),
+            exclude: Vec::default(),
+            constraints: Vec::default(),
+            shop_budget: None,
+            starting_items: Vec::default(),
+            randomize_teleporters: false,
+            randomize_doors: false,
+            keystone_costs: None,
+            generator: GeneratorConfig::default(),
+            world_settings: Vec::default(),
         }
     }
 }
@@ -93,6 +243,33 @@ impl Settings {
         let content = util::read_file(&preset, "presets")?;
         Settings::compability_parse(&content)
     }
+    /// The settings for one world of a multiworld, with its overrides applied
+    pub fn for_world(&self, world_index: usize) -> Settings {
+        let mut settings = self.clone();
+
+        if let Some(world_settings) = self.world_settings.get(world_index).cloned() {
+            let WorldSettings {
+                difficulty,
+                glitches,
+                spawn_loc,
+                header_list,
+                header_args,
+            } = world_settings;
+
+            override_with(&mut settings.difficulty, difficulty);
+            override_with(&mut settings.glitches, glitches);
+            override_with(&mut settings.spawn_loc, spawn_loc);
+            override_with(&mut settings.header_list, header_list);
+            override_with(&mut settings.header_args, header_args);
+        }
+
+        settings
+    }
+    /// Whether the logic of some worlds differs from the others and they need their own graph
+    pub fn has_world_logic(&self) -> bool {
+        (0..self.world_settings.len()).map(|world_index| self.for_world(world_index)).any(|world_settings| world_settings.difficulty != self.difficulty || world_settings.glitches != self.glitches)
+    }
+
     pub fn write(&self) -> Result<String, String> {
         serde_json::to_string(&self).map_err(|err| format!("Invalid Settings: {}", err))
     }
@@ -113,6 +290,15 @@ impl Settings {
             hard: other_hard,
             header_list: mut other_header_list,
             header_args: mut other_header_args,
+            exclude: mut other_exclude,
+            constraints: mut other_constraints,
+            shop_budget: other_shop_budget,
+            starting_items: mut other_starting_items,
+            randomize_teleporters: other_randomize_teleporters,
+            randomize_doors: other_randomize_doors,
+            keystone_costs: other_keystone_costs,
+            generator: other_generator,
+            world_settings: other_world_settings,
         } = other;
 
         if other_version.is_some() {
@@ -123,7 +309,13 @@ impl Settings {
             self.worlds = other_worlds;
             self.players = other_players;
         }
-        if self.difficulty < other_difficulty {
+        // tiers declared in the areas file can't be ranked without it, so they take precedence over the builtin ones
+        let harder = match (Difficulty::from_name(&self.difficulty), Difficulty::from_name(&other_difficulty)) {
+            (Some(difficulty), Some(other_difficulty)) => difficulty < other_difficulty,
+            (_, None) => true,
+            (None, Some(_)) => false,
+        };
+        if harder {
             self.difficulty = other_difficulty;
         }
         for glitch in other_glitches {
@@ -139,6 +331,25 @@ impl Settings {
         self.hard = self.hard || other_hard;
         self.header_list.append(&mut other_header_list);
         self.header_args.append(&mut other_header_args);
+        self.exclude.append(&mut other_exclude);
+        self.constraints.append(&mut other_constraints);
+        if other_shop_budget.is_some() {
+            self.shop_budget = other_shop_budget;
+        }
+        self.starting_items.append(&mut other_starting_items);
+        self.randomize_teleporters = self.randomize_teleporters || other_randomize_teleporters;
+        self.randomize_doors = self.randomize_doors || other_randomize_doors;
+        if other_keystone_costs.is_some() {
+            self.keystone_costs = other_keystone_costs;
+        }
+        self.generator.merge(other_generator);
+        for (world_index, other_world_settings) in other_world_settings.into_iter().enumerate() {
+            if let Some(world_settings) = self.world_settings.get_mut(world_index) {
+                world_settings.merge(other_world_settings);
+            } else {
+                self.world_settings.push(other_world_settings);
+            }
+        }
     }
     pub fn apply_presets(mut self) -> Result<Settings, String> {
         let mut merged_settings = Settings::default();
@@ -272,7 +483,7 @@ fn read_pre_rustgen(json: &str) -> Result<Settings, io::Error> {
     if old_settings.flags.world_tour { goalmodes.push(GoalMode::RelicChance(80.0)); }
 
     Ok(Settings {
-        difficulty,
+        difficulty: difficulty.name().to_string(),
         glitches,
         goalmodes,
         race: !old_settings.spoilers,
@@ -326,7 +537,7 @@ fn read_pre_0_13_2(json: &str) -> Result<Settings, io::Error> {
         presets: old_settings.presets,
         worlds: old_settings.worlds,
         players: old_settings.players,
-        difficulty,
+        difficulty: difficulty.name().to_string(),
         glitches,
         goalmodes: old_settings.goalmodes,
         spawn_loc: old_settings.spawn_loc,
@@ -336,6 +547,7 @@ fn read_pre_0_13_2(json: &str) -> Result<Settings, io::Error> {
         hard: old_settings.hard,
         header_list: old_settings.header_list,
         header_args: old_settings.header_args,
+        ..Settings::default()
     })
 }
 
@@ -365,7 +577,7 @@ fn read_pre_1_0_0(json: &str) -> Result<Settings, io::Error> {
         presets: old_settings.presets,
         worlds: old_settings.worlds,
         players: old_settings.players,
-        difficulty: old_settings.difficulty,
+        difficulty: old_settings.difficulty.name().to_string(),
         glitches: old_settings.glitches,
         goalmodes: old_settings.goalmodes,
         spawn_loc: old_settings.spawn_loc,
@@ -375,6 +587,7 @@ fn read_pre_1_0_0(json: &str) -> Result<Settings, io::Error> {
         hard: old_settings.hard,
         header_list: old_settings.header_list,
         header_args: old_settings.header_args,
+        ..Settings::default()
     })
 }
 
@@ -420,7 +633,7 @@ fn read_pre_1_0_10(json: &str) -> Result<Settings, io::Error> {
         presets: old_settings.presets,
         worlds: old_settings.worlds,
         players: old_settings.players,
-        difficulty: old_settings.difficulty,
+        difficulty: old_settings.difficulty.name().to_string(),
         glitches: old_settings.glitches,
         goalmodes,
         spawn_loc: old_settings.spawn_loc,
@@ -430,6 +643,7 @@ fn read_pre_1_0_10(json: &str) -> Result<Settings, io::Error> {
         hard: old_settings.hard,
         header_list: old_settings.header_list,
         header_args: old_settings.header_args,
+        ..Settings::default()
     })
 }
 
@@ -472,4 +686,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn merge() {
+        let mut settings = Settings::default();
+        settings.generator.retries = 20;
+        settings.world_settings = vec![WorldSettings { difficulty: Some(String::from("gorlek")), ..WorldSettings::default() }];
+
+        let mut other = Settings::default();
+        other.generator.progression_balancing = 0.5;
+        other.generator.avoid_softlocks = false;
+        other.world_settings = vec![WorldSettings { spawn_loc: Some(Spawn::Random), ..WorldSettings::default() }];
+        settings.merge(other);
+
+        assert_eq!(settings.generator, GeneratorConfig { retries: 20, progression_balancing: 0.5, avoid_softlocks: false, ..GeneratorConfig::default() });
+        assert_eq!(settings.world_settings, vec![WorldSettings { difficulty: Some(String::from("gorlek")), spawn_loc: Some(Spawn::Random), ..WorldSettings::default() }]);
+
+        let world_settings = settings.for_world(0);
+        assert_eq!((world_settings.difficulty.as_str(), world_settings.spawn_loc), ("gorlek", Spawn::Random));
+        assert_eq!(settings.for_world(1).difficulty, settings.difficulty);
+    }
 }
@@ -0,0 +1,316 @@
+pub mod playthrough;
+
+use std::{collections::HashMap, rc::Rc};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+
+use crate::{
+    ItemDetails,
+    generator::{self, Placement},
+    item::Item,
+    settings::Settings,
+    world::{
+        World,
+        graph::{Graph, Node},
+    },
+    util::{UberState, UberIdentifier},
+};
+
+#[derive(Debug, Serialize)]
+pub struct SpoilerPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpoilerPlacement {
+    pub uber_state: String,
+    pub item_code: String,
+    pub item_name: String,
+    pub location: Option<String>,
+    pub zone: Option<String>,
+    pub position: Option<SpoilerPosition>,
+}
+impl SpoilerPlacement {
+    fn new(placement: &Placement, graph: &Graph, custom_items: &HashMap<String, ItemDetails>) -> SpoilerPlacement {
+        let item = graph.seed_item(&placement.item);
+        let item_code = item.code();
+        let item_name = custom_items.get(&item_code)
+            .and_then(|details| details.name.clone())
+            .unwrap_or_else(|| item.to_string());
+
+        SpoilerPlacement {
+            uber_state: placement.uber_state.to_string(),
+            item_code,
+            item_name,
+            location: placement.node.map(|node| node.identifier().to_string()),
+            zone: placement.node.and_then(Node::zone).map(|zone| zone.to_string()),
+            position: placement.node.and_then(Node::position).map(|position| SpoilerPosition {
+                x: position.x.into_inner(),
+                y: position.y.into_inner(),
+            }),
+        }
+    }
+}
+
+/// Spoiler data for one world
+///
+/// `spheres` holds the placements in the order they become reachable, where every sphere contains what can be collected using the items from all previous spheres.
+/// In multiworld, the spheres of all worlds are computed together, so the same sphere index refers to the same point in time across worlds.
+/// `playthrough` holds only the pickups required to reach the goal, grouped the same way.
+/// `spirit_light` is the total Spirit Light this world receives, including what other worlds send to it.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldSpoiler {
+    pub spawn: String,
+    pub spirit_light: u32,
+    pub spheres: Vec<Vec<SpoilerPlacement>>,
+    pub playthrough: Vec<Vec<SpoilerPlacement>>,
+    pub unreachable: Vec<SpoilerPlacement>,
+}
+impl WorldSpoiler {
+    pub fn write(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(&self).map_err(|err| format!("Invalid Spoiler: {}", err))
+    }
+}
+
+pub(crate) type Sphere = Vec<Vec<usize>>;
+
+/// Lookup of the placements on each uberState, and of the placements received from other worlds through uberState group 12
+struct PlacementIndex<'b> {
+    locations: Vec<FxHashMap<&'b UberState, Vec<usize>>>,
+    received: Vec<FxHashMap<&'b UberIdentifier, Vec<usize>>>,
+}
+impl<'b> PlacementIndex<'b> {
+    fn new(placements: &'b [Vec<Placement>]) -> PlacementIndex<'b> {
+        let mut locations = vec![FxHashMap::<_, Vec<_>>::default(); placements.len()];
+        let mut received = vec![FxHashMap::<_, Vec<_>>::default(); placements.len()];
+
+        for (world_index, world_placements) in placements.iter().enumerate() {
+            for (placement_index, placement) in world_placements.iter().enumerate() {
+                locations[world_index].entry(&placement.uber_state).or_default().push(placement_index);
+                if placement.node.is_none() && placement.uber_state.identifier.uber_group == 12 {
+                    received[world_index].entry(&placement.uber_state.identifier).or_default().push(placement_index);
+                }
+            }
+        }
+
+        PlacementIndex { locations, received }
+    }
+}
+
+#[derive(Clone)]
+struct Replay<'a, 'b> {
+    worlds: Vec<World<'a>>,
+    spawns: &'b [&'a Node],
+    placements: &'b [Vec<Placement<'a>>],
+    index: Rc<PlacementIndex<'b>>,
+    skipped: FxHashSet<(usize, usize)>,
+    collected: Vec<Vec<bool>>,
+    visited: Vec<FxHashSet<usize>>,
+    changed: Vec<bool>,
+}
+impl<'a, 'b> Replay<'a, 'b> {
+    /// Prepare to replay the placements, the items of placements in `skipped` are not granted when collecting them
+    fn new(worlds: Vec<World<'a>>, spawns: &'b [&'a Node], placements: &'b [Vec<Placement<'a>>], skipped: FxHashSet<(usize, usize)>) -> Replay<'a, 'b> {
+        let world_count = worlds.len();
+
+        Replay {
+            worlds,
+            spawns,
+            placements,
+            index: Rc::new(PlacementIndex::new(placements)),
+            skipped,
+            collected: placements.iter().map(|world_placements| vec![false; world_placements.len()]).collect(),
+            visited: vec![FxHashSet::default(); world_count],
+            changed: vec![true; world_count],
+        }
+    }
+
+    fn at_location(&self, world_index: usize, uber_state: &UberState) -> Vec<usize> {
+        self.index.locations[world_index].get(uber_state).cloned().unwrap_or_default()
+    }
+
+    /// Whether granting the item might make anything new reachable
+    fn is_useful(&self, world_index: usize, item: &Item) -> bool {
+        match item {
+            Item::SpiritLight(_) => true,
+            Item::UberState(command) if command.uber_identifier.uber_group == 12 =>
+                (0..self.worlds.len()).filter(|&target_world_index| target_world_index != world_index).any(|target_world_index|
+                    self.index.received[target_world_index].get(&command.uber_identifier).map_or(false, |received|
+                        received.iter().any(|&target_placement_index| self.is_useful(target_world_index, &self.placements[target_world_index][target_placement_index].item)))),
+            Item::UberState(_) => true,
+            item => item.is_progression(self.worlds[world_index].player.difficulty),
+        }
+    }
+
+    fn collect(&mut self, world_index: usize, placement_index: usize, sphere: &mut Sphere) {
+        if self.collected[world_index][placement_index] { return; }
+        self.collected[world_index][placement_index] = true;
+        sphere[world_index].push(placement_index);
+
+        if self.skipped.contains(&(world_index, placement_index)) { return; }
+
+        let item = &self.placements[world_index][placement_index].item;
+        self.changed[world_index] = true;
+        self.worlds[world_index].grant_player(item.clone(), 1).unwrap_or_else(|err| log::error!("{}", err));
+
+        // multiworld items are sent through uberState group 12 and received by the other worlds
+        if let Item::UberState(command) = item {
+            if command.uber_identifier.uber_group == 12 {
+                for target_world_index in (0..self.worlds.len()).filter(|&target_world_index| target_world_index != world_index) {
+                    let received = self.index.received[target_world_index].get(&command.uber_identifier).cloned().unwrap_or_default();
+                    for target_placement_index in received {
+                        self.collect(target_world_index, target_placement_index, sphere);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collect everything granted on spawn or load
+    fn spawn_sphere(&mut self) -> Sphere {
+        let spawn = UberState::spawn();
+        let load = UberState::load();
+
+        let mut sphere = vec![Vec::new(); self.worlds.len()];
+        for world_index in 0..self.worlds.len() {
+            self.worlds[world_index].collect_preplacements(&spawn);
+
+            for placement_index in self.at_location(world_index, &spawn).into_iter().chain(self.at_location(world_index, &load)) {
+                self.collect(world_index, placement_index, &mut sphere);
+            }
+        }
+
+        sphere
+    }
+
+    /// Collect everything that became reachable with the items collected so far, returns `None` once nothing new can be reached
+    fn next_sphere(&mut self) -> Result<Option<Sphere>, String> {
+        let world_count = self.worlds.len();
+
+        let mut reached = Vec::with_capacity(world_count);
+        for world_index in 0..world_count {
+            // nothing new can be reached in a world that didn't change since the last sphere
+            if !self.changed[world_index] {
+                reached.push(Vec::new());
+                continue;
+            }
+
+            let world = &self.worlds[world_index];
+            let world_visited = &mut self.visited[world_index];
+
+            let mut world_reached = world.graph.reached_locations(&world.player, self.spawns[world_index], &world.uber_states, &world.sets)?;
+            world_reached.retain(|&node| node.uber_state().is_some() && world_visited.insert(node.index()));
+
+            self.changed[world_index] = !world_reached.is_empty();
+            reached.push(world_reached);
+        }
+
+        if reached.iter().all(Vec::is_empty) { return Ok(None); }
+
+        let mut sphere = vec![Vec::new(); world_count];
+        for (world_index, world_reached) in reached.into_iter().enumerate() {
+            for node in world_reached {
+                let uber_state = node.uber_state().unwrap();
+                self.worlds[world_index].collect_preplacements(uber_state);
+
+                for placement_index in self.at_location(world_index, uber_state) {
+                    self.collect(world_index, placement_index, &mut sphere);
+                }
+            }
+        }
+
+        Ok(Some(sphere))
+    }
+}
+
+/// Replay the placements and group them into the spheres in which they get collected, along with which placements were collected at all
+pub(crate) fn spheres<'a>(worlds: Vec<World<'a>>, spawns: &[&'a Node], placements: &[Vec<Placement<'a>>]) -> Result<(Vec<Sphere>, Vec<Vec<bool>>), String> {
+    let mut replay = Replay::new(worlds, spawns, placements, FxHashSet::default());
+    let mut spheres = vec![replay.spawn_sphere()];
+
+    while let Some(sphere) = replay.next_sphere()? {
+        if sphere.iter().any(|world_sphere| !world_sphere.is_empty()) {
+            log::trace!("Spoiler sphere {}: {} placements", spheres.len(), sphere.iter().map(Vec::len).sum::<usize>());
+            spheres.push(sphere);
+        }
+    }
+
+    Ok((spheres, replay.collected))
+}
+
+pub fn generate_spoilers<'a>(worlds: Vec<World<'a>>, spawns: &[&'a Node], placements: &[Vec<Placement<'a>>], custom_items: &[HashMap<String, ItemDetails>], settings: &Settings) -> Result<Vec<WorldSpoiler>, String> {
+    let world_count = worlds.len();
+
+    let playthrough = playthrough::playthrough(&worlds, spawns, placements, settings)?;
+    let graphs = worlds.iter().map(|world| world.graph).collect::<Vec<_>>();
+    let (spheres, collected) = spheres(worlds, spawns, placements)?;
+
+    let world_spoilers = (0..world_count).map(|world_index| {
+        let world_placements = &placements[world_index];
+        let graph = graphs[world_index];
+        let custom_items = &custom_items[world_index];
+
+        let to_spoiler = |spheres: &[Sphere]| spheres.iter()
+            .map(|sphere| sphere[world_index].iter()
+                .map(|&placement_index| SpoilerPlacement::new(&world_placements[placement_index], graph, custom_items))
+                .collect())
+            .collect();
+        let unreachable = world_placements.iter()
+            .zip(&collected[world_index])
+            .filter(|(_, &collected)| !collected)
+            .map(|(placement, _)| SpoilerPlacement::new(placement, graph, custom_items))
+            .collect();
+
+        WorldSpoiler {
+            spawn: spawns[world_index].identifier().to_string(),
+            spirit_light: generator::spirit_light_total(world_placements),
+            spheres: to_spoiler(&spheres),
+            playthrough: to_spoiler(&playthrough),
+            unreachable,
+        }
+    }).collect();
+
+    Ok(world_spoilers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{settings::Settings, languages, generate_seed};
+
+    #[test]
+    fn spheres() {
+        let mut settings = Settings::default();
+        settings.worlds = 2;
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let (seeds, _, json_spoilers) = generate_seed(&graph, settings, &Vec::new(), None, true).unwrap();
+        assert_eq!(json_spoilers.len(), 2);
+
+        for (seed, json_spoiler) in seeds.iter().zip(&json_spoilers) {
+            let spoiler: serde_json::Value = serde_json::from_str(json_spoiler).unwrap();
+
+            let spheres = spoiler["spheres"].as_array().unwrap();
+            assert!(spheres.len() > 1);
+
+            let sphere_placements = spheres.iter().map(|sphere| sphere.as_array().unwrap().len()).sum::<usize>();
+            let unreachable = spoiler["unreachable"].as_array().unwrap();
+            let placement_lines = seed.lines().filter(|line| line.contains("  // ")).count();
+            assert_eq!(sphere_placements + unreachable.len(), placement_lines);
+
+            for placement in unreachable {
+                let item_code = placement["itemCode"].as_str().unwrap();
+                assert!(item_code.starts_with("0|") || item_code == "1|2", "{} is unreachable", placement);
+            }
+
+            let playthrough = spoiler["playthrough"].as_array().unwrap();
+            assert!(!playthrough.is_empty());
+            for placement in playthrough.iter().flat_map(|sphere| sphere.as_array().unwrap()) {
+                assert!(spheres.iter().any(|sphere| sphere.as_array().unwrap().contains(placement)), "{} is in the playthrough but was never reached", placement);
+            }
+        }
+    }
+}
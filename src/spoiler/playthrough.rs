@@ -0,0 +1,185 @@
+use rustc_hash::FxHashSet;
+
+use super::{Replay, Sphere};
+use crate::{
+    generator::{self, Placement},
+    item::Item,
+    settings::Settings,
+    world::{
+        World,
+        graph::{Graph, Node},
+    },
+    util::{
+        GoalMode, UberState,
+        constants::{WILLOW_HEARTS, WISP_STATES},
+    },
+};
+
+/// Conditions which are met once any of their nodes is visited, along with how many conditions have to be met
+struct Goal {
+    conditions: Vec<Vec<usize>>,
+    required: usize,
+}
+impl Goal {
+    fn all(nodes: Vec<usize>) -> Goal {
+        Goal {
+            required: nodes.len(),
+            conditions: nodes.into_iter().map(|index| vec![index]).collect(),
+        }
+    }
+    fn met_count(&self, visited: &FxHashSet<usize>) -> usize {
+        self.conditions.iter().filter(|nodes| nodes.iter().any(|index| visited.contains(index))).count()
+    }
+}
+
+fn goals(graph: &Graph, placements: &[Placement], settings: &Settings) -> Result<Vec<Goal>, String> {
+    let mut goal = graph.nodes.iter()
+        .filter(|&node| WILLOW_HEARTS.contains(&node.identifier()))
+        .map(Node::index)
+        .collect::<Vec<_>>();
+    let mut custom_goals = Vec::new();
+
+    for goalmode in &settings.goalmodes {
+        match goalmode {
+            GoalMode::Trees => goal.extend(graph.nodes.iter()
+                .filter(|&node| matches!(node, Node::Pickup(pickup) if pickup.uber_state.identifier.uber_group == 0))
+                .map(Node::index)),
+            GoalMode::Wisps => goal.extend(graph.nodes.iter()
+                .filter(|&node| node.uber_state().map_or(false, |uber_state| WISP_STATES.contains(&uber_state.identifier)))
+                .map(Node::index)),
+            GoalMode::Quests => goal.extend(graph.nodes.iter()
+                .filter(|&node| matches!(node, Node::Quest(_)))
+                .map(Node::index)),
+            GoalMode::Relics(_) | GoalMode::RelicChance(_) => goal.extend(placements.iter()
+                .filter(|&placement| matches!(placement.item, Item::Relic(_)))
+                .filter_map(|placement| placement.node)
+                .map(Node::index)),
+            GoalMode::Custom(custom_goal) => {
+                let conditions = generator::custom_goal_conditions(graph, custom_goal)?;
+                custom_goals.push(Goal {
+                    required: custom_goal.required.unwrap_or(conditions.len()),
+                    conditions,
+                });
+            },
+        }
+    }
+
+    goal.sort_unstable();
+    goal.dedup();
+
+    custom_goals.push(Goal::all(goal));
+    Ok(custom_goals)
+}
+
+fn reaches_goal(snapshot: &Replay, skipped: FxHashSet<(usize, usize)>, goals: &[Vec<Goal>]) -> Result<bool, String> {
+    let mut replay = snapshot.clone();
+    replay.skipped = skipped;
+
+    loop {
+        if goals.iter().zip(&replay.visited).all(|(goals, visited)| goals.iter().all(|goal| goal.met_count(visited) >= goal.required)) {
+            return Ok(true);
+        }
+        if replay.next_sphere()?.is_none() {
+            return Ok(false);
+        }
+    }
+}
+
+// most pickups aren't needed, so try to skip them in groups and only split the group up if that fails
+fn prune(group: &[(usize, usize)], snapshot: &Replay, skipped: &mut FxHashSet<(usize, usize)>, goals: &[Vec<Goal>]) -> Result<(), String> {
+    if group.is_empty() { return Ok(()); }
+
+    let mut group_skipped = skipped.clone();
+    group_skipped.extend(group.iter().copied());
+    if reaches_goal(snapshot, group_skipped.clone(), goals)? {
+        *skipped = group_skipped;
+    } else if group.len() > 1 {
+        let (first_half, second_half) = group.split_at(group.len() / 2);
+        prune(second_half, snapshot, skipped, goals)?;
+        prune(first_half, snapshot, skipped, goals)?;
+    }
+
+    Ok(())
+}
+
+/// Determine which pickups are actually required to reach the goal
+///
+/// Starting from the last sphere, every pickup holding a potentially useful item is left out of the replay as long as the goal stays reachable without it.
+/// Returns the remaining pickups grouped into the spheres in which they get collected.
+pub fn playthrough<'a>(worlds: &[World<'a>], spawns: &[&'a Node], placements: &[Vec<Placement<'a>>], settings: &Settings) -> Result<Vec<Sphere>, String> {
+    let world_count = worlds.len();
+
+    let spawn = UberState::spawn();
+    let load = UberState::load();
+
+    let mut replay = Replay::new(worlds.to_vec(), spawns, placements, FxHashSet::default());
+    let mut spheres = vec![replay.spawn_sphere()];
+    // since pickups are only skipped starting from the last sphere, every check can resume from the state before the sphere in question
+    let mut snapshots = Vec::new();
+    loop {
+        let snapshot = replay.clone();
+        if let Some(sphere) = replay.next_sphere()? {
+            snapshots.push(snapshot);
+            spheres.push(sphere);
+        } else { break; }
+    }
+
+    let goals = (0..world_count).map(|world_index| {
+        let mut goals = goals(worlds[world_index].graph, &placements[world_index], settings)?;
+
+        let visited = &replay.visited[world_index];
+        let mut unreachable = 0;
+        for goal in &mut goals {
+            let condition_count = goal.conditions.len();
+            goal.conditions.retain(|nodes| nodes.iter().any(|index| visited.contains(index)));
+            unreachable += condition_count - goal.conditions.len();
+            goal.required = goal.required.min(goal.conditions.len());
+        }
+        if unreachable > 0 {
+            let player_name = settings.players.get(world_index).cloned().unwrap_or_else(|| format!("Player {}", world_index + 1));
+            log::trace!("({}): {} goal conditions are unreachable and will be ignored for the playthrough", player_name, unreachable);
+        }
+
+        Ok(goals)
+    }).collect::<Result<Vec<_>, String>>()?;
+
+    let candidates = spheres.iter().map(|sphere| {
+        let mut sphere_candidates = sphere.iter().enumerate()
+            .flat_map(|(world_index, world_sphere)| world_sphere.iter().map(move |&placement_index| (world_index, placement_index)))
+            .filter(|&(world_index, placement_index)| {
+                let placement = &placements[world_index][placement_index];
+
+                placement.node.is_some() && placement.uber_state != spawn && placement.uber_state != load &&
+                replay.is_useful(world_index, &placement.item)
+            })
+            .collect::<Vec<_>>();
+        sphere_candidates.sort_by_key(|&(world_index, placement_index)| matches!(placements[world_index][placement_index].item, Item::SpiritLight(_)));
+        sphere_candidates
+    }).collect::<Vec<_>>();
+
+    // the spawn sphere has no candidates, so candidates[index + 1] belong to snapshots[index]
+    let mut skipped = FxHashSet::default();
+    for (sphere_candidates, snapshot) in candidates.iter().skip(1).zip(&snapshots).rev() {
+        prune(sphere_candidates, snapshot, &mut skipped, &goals)?;
+    }
+
+    let required = candidates.into_iter().flatten().filter(|candidate| !skipped.contains(candidate)).collect::<FxHashSet<_>>();
+    log::trace!("Playthrough requires {} pickups", required.len());
+
+    let mut replay = Replay::new(worlds.to_vec(), spawns, placements, skipped);
+    let mut spheres = vec![replay.spawn_sphere()];
+    while let Some(sphere) = replay.next_sphere()? {
+        spheres.push(sphere);
+    }
+
+    let playthrough = spheres.into_iter()
+        .map(|sphere| sphere.into_iter().enumerate()
+            .map(|(world_index, world_sphere)| world_sphere.into_iter()
+                .filter(|&placement_index| required.contains(&(world_index, placement_index)))
+                .collect::<Vec<_>>())
+            .collect::<Sphere>())
+        .filter(|sphere| sphere.iter().any(|world_sphere| !world_sphere.is_empty()))
+        .collect();
+
+    Ok(playthrough)
+}
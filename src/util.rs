@@ -25,6 +25,21 @@ pub enum Difficulty {
 impl Default for Difficulty {
     fn default() -> Difficulty { Difficulty::Moki }
 }
+impl Difficulty {
+    /// The name of the difficulty tier in the logic and settings
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Moki => "moki",
+            Difficulty::Gorlek => "gorlek",
+            Difficulty::Kii => "kii",
+            Difficulty::Unsafe => "unsafe",
+        }
+    }
+    /// The builtin difficulty with this tier name, if it is one
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        [Difficulty::Moki, Difficulty::Gorlek, Difficulty::Kii, Difficulty::Unsafe].iter().copied().find(|difficulty| difficulty.name().eq_ignore_ascii_case(name))
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum Glitch {
@@ -52,6 +67,18 @@ pub enum Glitch {
     SpearJump,          // Storing a grounded jump into the air with Spear
 }
 
+/// A goal made up of logic states, pickups or uber states that have to be reached
+///
+/// Conditions are either node identifiers from the areas file or uber states like "group|id" or "group|id=value".
+/// If `required` is set, only that many of the conditions have to be met.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CustomGoal {
+    pub name: String,
+    pub conditions: Vec<String>,
+    #[serde(default)]
+    pub required: Option<usize>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum GoalMode {
     Wisps,
@@ -59,6 +86,7 @@ pub enum GoalMode {
     Quests,
     Relics(usize),
     RelicChance(f64),
+    Custom(CustomGoal),
 }
 impl fmt::Display for GoalMode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -67,6 +95,7 @@ impl fmt::Display for GoalMode {
             GoalMode::Trees => write!(f, "ForceTrees"),
             GoalMode::Quests => write!(f, "ForceQuests"),
             GoalMode::Relics(_) | GoalMode::RelicChance(_) => write!(f, "WorldTour"),
+            GoalMode::Custom(goal) => write!(f, "{}", goal.name),
         }
     }
 }
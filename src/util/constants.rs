@@ -43,19 +43,20 @@ pub const RELIC_ZONES: &[Zone] = &[
     Zone::Willow,
     Zone::Burrows,
 ];
-pub const KEYSTONE_DOORS: &[(&str, u16)] = &[
-    ("MarshSpawn.KeystoneDoor", 2),
-    ("HowlsDen.KeystoneDoor", 2),
-    ("MarshPastOpher.EyestoneDoor", 2),
-    ("MidnightBurrows.KeystoneDoor", 4),
-    ("WoodsEntry.KeystoneDoor", 2),
-    ("WoodsMain.KeystoneDoor", 4),
-    ("LowerReach.KeystoneDoor", 4),
-    ("UpperReach.KeystoneDoor", 4),
-    ("UpperDepths.EntryKeystoneDoor", 2),
-    ("UpperDepths.CentralKeystoneDoor", 2),
-    ("UpperPools.KeystoneDoor", 4),
-    ("UpperWastes.KeystoneDoor", 2),
+pub const KEYSTONE_COST_GROUP: u16 = 29;  // dedicated uber group the randomizer reads door costs from, using the ids in KEYSTONE_DOORS. No header may use this group
+pub const KEYSTONE_DOORS: &[(&str, u16, u16)] = &[
+    ("MarshSpawn.KeystoneDoor", 2, 0),
+    ("HowlsDen.KeystoneDoor", 2, 1),
+    ("MarshPastOpher.EyestoneDoor", 2, 2),
+    ("MidnightBurrows.KeystoneDoor", 4, 3),
+    ("WoodsEntry.KeystoneDoor", 2, 4),
+    ("WoodsMain.KeystoneDoor", 4, 5),
+    ("LowerReach.KeystoneDoor", 4, 6),
+    ("UpperReach.KeystoneDoor", 4, 7),
+    ("UpperDepths.EntryKeystoneDoor", 2, 8),
+    ("UpperDepths.CentralKeystoneDoor", 2, 9),
+    ("UpperPools.KeystoneDoor", 4, 10),
+    ("UpperWastes.KeystoneDoor", 2, 11),
 ];
 
 pub const WISP_STATES: &[UberIdentifier] = &[
@@ -81,10 +82,16 @@ pub const WISP_STATES: &[UberIdentifier] = &[
     },
 ];
 
-pub const RESERVE_SLOTS: usize = 1;  // how many slots to reserve after random placements for the next iteration
-pub const PLACEHOLDER_SLOTS: usize = 25;  // how many slots to keep as placeholders for bigger progressions
-pub const RETRIES: u16 = 10;  // How many retries to allow when generating a seed
-pub const RANDOM_PROGRESSION: f64 = 0.4;  // How likely to choose a progression item as random placement
+pub const WILLOW_HEARTS: &[&str] = &[  // states that have to be met before the final escape, only those present in the logic are used
+    "WillowsEnd.GrappleWheelsHeart",
+    "WillowsEnd.BoulderHeart",
+    "WillowsEnd.RedirectHeart",
+    "WillowsEnd.BurrowHeart",
+    "WillowsEnd.MinibossHeart",
+    "WillowsEnd.SpinLasersHeart",
+    "WillowsEnd.GlideHeart",
+    "WillowsEnd.SpinPortalsHeart",
+];
 
 pub const HEADER_INDENT: usize = 24;  // Which column to align header descriptions on
 pub const NAME_COLOUR: Colour = Colour::Yellow;
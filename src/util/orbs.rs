@@ -96,6 +96,22 @@ pub fn both_single(a: &[Orbs], b: Orbs) -> SmallVec<[Orbs; 3]> {
         }).cloned().collect()
     }
 }
+/// Whether `b` has an option that no option in `a` is at least as good as
+pub fn improves(a: &[Orbs], b: &[Orbs]) -> bool {
+    b.iter().any(|b_| !a.iter().any(|a_| a_.energy >= b_.energy && a_.health >= b_.health))
+}
+/// All options from either `a` or `b` that aren't worse than another one
+pub fn union(a: &[Orbs], b: &[Orbs]) -> SmallVec<[Orbs; 3]> {
+    let mut union = SmallVec::<[Orbs; 3]>::with_capacity(a.len() + b.len());
+    for orbs in a.iter().chain(b) {
+        if !union.contains(orbs) {
+            union.push(*orbs);
+        }
+    }
+    union.iter().filter(|orbs| {
+        !union.iter().any(|other| other.energy > orbs.energy && other.health >= orbs.health || other.energy >= orbs.energy && other.health > orbs.health)
+    }).cloned().collect()
+}
 
 #[cfg(test)]
 mod tests {
@@ -24,7 +24,7 @@ impl<'a> World<'a> {
     pub fn new(graph: &Graph) -> World {
         World {
             graph,
-            player: Player::default(),
+            player: Player { difficulty: graph.difficulty, ..Player::default() },
             pool: Pool::default(),
             preplacements: FxHashMap::default(),
             uber_states: FxHashMap::default(),
@@ -152,7 +152,7 @@ mod tests {
 
     use super::*;
     use super::super::*;
-    use world::pool::Pool;
+    use world::{pool::Pool, requirements::Requirement};
     use item::*;
     use util::*;
     use rustc_hash::FxHashSet;
@@ -160,7 +160,7 @@ mod tests {
     #[test]
     fn reach_check() {
         let mut settings = Settings::default();
-        settings.difficulty = Difficulty::Gorlek;
+        settings.difficulty = String::from("gorlek");
 
         let graph = &languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
         let mut world = World::new(graph);
@@ -189,7 +189,7 @@ mod tests {
         assert_eq!(reached, locations);
 
         let mut settings = Settings::default();
-        settings.difficulty = Difficulty::Gorlek;
+        settings.difficulty = String::from("gorlek");
 
         let graph = &languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
         let mut world = World::new(graph);
@@ -205,4 +205,297 @@ mod tests {
         let reached: Vec<_> = reached.iter().filter_map(|node| node.uber_state()).cloned().collect();
         assert_eq!(reached, vec![UberState::from_parts("42178", "63404").unwrap(), UberState::from_parts("42178", "42762").unwrap(), UberState::from_parts("23987", "14014").unwrap(), UberState::from_parts("42178", "6117").unwrap()]);
     }
+
+    #[test]
+    fn shuffle_teleporters() {
+        use rand::SeedableRng;
+
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+
+        let shuffled = graph.shuffle_teleporters(&[Teleporter::Glades], &mut rng).unwrap();
+        assert!(!shuffled.redirects.is_empty());
+        let teleporters = [Teleporter::Marsh, Teleporter::Den, Teleporter::Hollow, Teleporter::Glades, Teleporter::Burrows, Teleporter::Willow];
+        let mut accesses = teleporters.iter().map(|&teleporter| shuffled.teleporter_access(teleporter)).collect::<Vec<_>>();
+        accesses.sort_by_key(|&teleporter| teleporter as u8);
+        accesses.dedup();
+        assert_eq!(accesses.len(), teleporters.len());
+        for redirect in &shuffled.redirects {
+            assert_ne!(redirect.access, redirect.destination);
+            assert_eq!(shuffled.teleporter_destination(redirect.access), redirect.destination);
+            assert_eq!(shuffled.seed_item(&Item::Teleporter(redirect.access)), Item::Teleporter(redirect.destination));
+        }
+        assert_eq!(shuffled.teleporter_access(Teleporter::Glades), Teleporter::Glades);
+        assert_eq!(shuffled.seed_item(&Item::Teleporter(Teleporter::Glades)), Item::Teleporter(Teleporter::Glades));
+    }
+
+    #[test]
+    fn shuffle_doors() {
+        use rand::SeedableRng;
+        use graph::DoorRedirect;
+
+        let areas = "anchor Test.A at 0, 0:\n  door Test.B 1:\n    moki: free\n  conn Test.C: free\n\nanchor Test.B at 0, 0:\n  door Test.A 2:\n    moki: free\n\nanchor Test.C at 0, 0:\n  door Test.D 3:\n    moki: free\n\nanchor Test.D at 0, 0:\n  door Test.C 4:\n    moki: free\n";
+        let (tokens, metadata) = logic::tokenizer::tokenize(areas).unwrap();
+        let areas = logic::parser::parse_areas(tokens, &metadata).unwrap();
+        let graph = logic::emitter::emit(&areas, &metadata, &[], &[], &Settings::default(), false).unwrap();
+        let door_targets = |graph: &Graph| graph.nodes.iter()
+            .filter_map(|node| if let Node::Anchor(anchor) = node { Some(anchor) } else { None })
+            .flat_map(|anchor| anchor.connections.iter().filter_map(|connection| connection.door.map(|door| (door, connection.to))))
+            .collect::<Vec<_>>();
+        let vanilla = door_targets(&graph);
+        assert_eq!(vanilla.iter().map(|&(door, _)| door).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+
+        let shuffled = (0..).map(|seed| graph.shuffle_doors(&mut rand::rngs::StdRng::seed_from_u64(seed))).find(|shuffled| !shuffled.door_redirects.is_empty()).unwrap();
+        let shuffled_targets = door_targets(&shuffled);
+        let mut targets = shuffled_targets.iter().map(|&(_, to)| to).collect::<Vec<_>>();
+        targets.sort_unstable();
+        let mut vanilla_targets = vanilla.iter().map(|&(_, to)| to).collect::<Vec<_>>();
+        vanilla_targets.sort_unstable();
+        assert_eq!(targets, vanilla_targets);
+        // every moved door is redirected to the door that usually leads to its new target
+        for ((intercept, to), (_, vanilla_to)) in shuffled_targets.into_iter().zip(vanilla.iter().copied()) {
+            let set = vanilla.iter().find(|&&(_, vanilla_to)| vanilla_to == to).unwrap().0;
+            assert_eq!(shuffled.door_redirects.contains(&DoorRedirect { intercept, set }), to != vanilla_to);
+        }
+
+        assert!(logic::tokenizer::tokenize("anchor Test.A at 0, 0:\n  door Test.B:\n    moki: free\n").and_then(|(tokens, metadata)| logic::parser::parse_areas(tokens, &metadata).map_err(|err| err.description)).is_err());
+    }
+
+    #[test]
+    fn traps() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut world = World::new(&graph);
+        world.player.apply_settings(&settings);
+
+        let spawn = graph.find_spawn("MarshSpawn.Cave").unwrap();
+        assert!(graph.traps(&world.player, spawn, &world.uber_states, &world.sets).unwrap().is_empty());
+
+        let spawn = graph.find_spawn("GladesTown.HoleHut").unwrap();
+        let traps = graph.traps(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+        assert!(traps.iter().any(|node| node.identifier() == "GladesTown.Teleporter"));
+
+        world.player.inventory.grant(Item::Teleporter(Teleporter::Glades), 1);
+        let traps = graph.traps(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+        assert!(!traps.iter().any(|node| node.identifier() == "GladesTown.Teleporter"));
+    }
+
+    #[test]
+    fn export() {
+        use graph::ExportFormat;
+        use requirements::Requirement;
+
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let requirement = Requirement::Or(vec![Requirement::Skill(Skill::DoubleJump), Requirement::And(vec![Requirement::Skill(Skill::Bash), Requirement::EnergySkill(Skill::Grenade, 1.0)])]);
+        assert_eq!(graph.display_requirement(&requirement), "DoubleJump OR (Bash, Grenade=1)");
+
+        let dot = graph.export(ExportFormat::Dot, &[Zone::Marsh]);
+        assert!(dot.starts_with("digraph logic {"));
+        assert!(dot.contains("\"MarshSpawn.Main\" -> \"MarshSpawn.RockHC\""));
+        assert!(!dot.contains("GladesTown.HandToHandPouch"));
+
+        let graphml = graph.export(ExportFormat::GraphML, &[]);
+        assert!(graphml.contains("<node id=\"GladesTown.HandToHandPouch\">"));
+        assert_eq!(graphml.matches("<edge ").count(), graph.nodes.iter().map(|node| if let Node::Anchor(anchor) = node { anchor.connections.len() } else { 0 }).sum::<usize>());
+    }
+
+    #[test]
+    fn explain_unreachable() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut world = World::new(&graph);
+        world.player.apply_settings(&settings);
+        world.player.spawn(&settings);
+
+        let spawn = graph.find_spawn("MarshSpawn.Main").unwrap();
+        let target = graph.nodes.iter().find(|node| node.identifier() == "MarshSpawn.RockHC").unwrap();
+        assert!(graph.explain_unreachable(&world.player, spawn, target, &world.uber_states, &world.sets, 3).is_none());
+
+        let target = graph.nodes.iter().find(|node| node.identifier() == "MarshSpawn.GrappleHC").unwrap();
+        let blocked = graph.explain_unreachable(&world.player, spawn, target, &world.uber_states, &world.sets, 3).unwrap();
+        assert_eq!(blocked[0].from.identifier(), "MarshSpawn.Main");
+        assert_eq!(blocked[0].to.identifier(), "MarshSpawn.GrappleHC");
+        assert!(blocked[0].unlocks.iter().all(|unlock| unlock.has(&Item::Skill(Skill::Grapple), 1)));
+        assert_eq!(blocked[0].unlocks.len(), 2);
+    }
+
+    #[test]
+    fn find_route() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut world = World::new(&graph);
+        world.player.inventory = Pool::preset().inventory;
+        world.player.inventory.grant(Item::SpiritLight(1), 10000);
+        world.player.apply_settings(&settings);
+
+        let start = graph.find_spawn("MarshSpawn.Main").unwrap();
+        let target = graph.nodes.iter().find(|node| node.identifier() == "WindtornRuins.Seir").unwrap();
+        let route = graph.find_route(&world.player, start, target, &world.uber_states, &world.sets).unwrap();
+
+        assert_eq!(route.first().unwrap().node.index(), start.index());
+        assert_eq!(route.last().unwrap().node.index(), target.index());
+        for steps in route.windows(2) {
+            if let Node::Anchor(anchor) = steps[0].node {
+                assert!(steps[1].requirement.is_none() || anchor.connections.iter().any(|connection| connection.to == steps[1].node.index()));
+            } else { panic!("Route continued from {}, which isn't an anchor", steps[0].node); }
+            assert!(!steps[1].orbs.is_empty());
+        }
+
+        world.player.inventory = inventory::Inventory::default();
+        assert!(graph.find_route(&world.player, start, target, &world.uber_states, &world.sets).is_none());
+    }
+
+    #[test]
+    fn find_route_revisits() {
+        // the direct connection to Test.Middle leaves too little health to continue, so the route has to take the detour
+        let areas = "anchor Test.Start at 0, 0:\n  conn Test.Middle:\n    moki: Damage=20\n  conn Test.Detour: free\n\nanchor Test.Detour at 0, 0:\n  conn Test.Middle: free\n\nanchor Test.Middle at 0, 0:\n  conn Test.Goal:\n    moki: Damage=20\n\nanchor Test.Goal at 0, 0:\n  conn Test.Start: free\n";
+        let (tokens, metadata) = logic::tokenizer::tokenize(areas).unwrap();
+        let areas = logic::parser::parse_areas(tokens, &metadata).unwrap();
+        let settings = Settings::default();
+        let graph = logic::emitter::emit(&areas, &metadata, &[], &[], &settings, false).unwrap();
+        let mut world = World::new(&graph);
+        world.player.spawn(&settings);
+
+        let start = graph.nodes.iter().find(|node| node.identifier() == "Test.Start").unwrap();
+        let target = graph.nodes.iter().find(|node| node.identifier() == "Test.Goal").unwrap();
+        let route = graph.find_route(&world.player, start, target, &world.uber_states, &world.sets).unwrap();
+        let route = route.iter().map(|step| step.node.identifier()).collect::<Vec<_>>();
+        assert_eq!(route, vec!["Test.Start", "Test.Detour", "Test.Middle", "Test.Goal"]);
+    }
+
+    #[test]
+    fn resume_reach() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut world = World::new(&graph);
+        world.player.spawn(&settings);
+        let spawn = graph.find_spawn("MarshSpawn.Main").unwrap();
+
+        let mut reach_state = None;
+        let items = [Item::Skill(Skill::DoubleJump), Item::Skill(Skill::Bash), Item::Resource(Resource::Health), Item::Water, Item::Skill(Skill::Grapple), Item::Skill(Skill::Glide), Item::SpiritLight(1)];
+        for item in items {
+            world.grant_player(item, 1).unwrap();
+
+            let (resumed, resumed_progressions) = graph.resume_reach(&world.player, spawn, &world.uber_states, &world.sets, &mut reach_state).unwrap();
+            let (reached, progressions) = graph.reached_and_progressions(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+
+            let mut resumed = resumed.iter().map(|node| node.index()).collect::<Vec<_>>();
+            let mut reached = reached.iter().map(|node| node.index()).collect::<Vec<_>>();
+            resumed.sort_unstable();
+            reached.sort_unstable();
+            assert_eq!(resumed, reached);
+            assert_eq!(resumed_progressions.is_empty(), progressions.is_empty());
+        }
+    }
+
+    #[test]
+    fn improved_orbs() {
+        // the direct way to the ledge is taken first and costs health, the detour gets there with more health later
+        let areas = "anchor Test.Start at 0, 0:\n  conn Test.Ledge:\n    moki: Damage=20\n  conn Test.Detour: free\n\nanchor Test.Detour at 0, 0:\n  conn Test.Ledge: free\n\nanchor Test.Ledge at 0, 0:\n  pickup Test.Pickup:\n    moki: Damage=20\n";
+        let locations = "Test.Pickup, Inkwater Marsh, Resource, Life, swampStateGroup, 21786, healthContainerA, 60210, -958, -4313\n";
+
+        let (tokens, metadata) = logic::tokenizer::tokenize(areas).unwrap();
+        let areas = logic::parser::parse_areas(tokens, &metadata).unwrap();
+        let locations = logic::parse_locations(locations).unwrap();
+        let settings = Settings::default();
+        let graph = logic::emitter::emit(&areas, &metadata, &locations, &[], &settings, false).unwrap();
+
+        let mut world = World::new(&graph);
+        world.player.spawn(&settings);
+        let spawn = graph.find_spawn("Test.Start").unwrap();
+
+        let reached = graph.reached_locations(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+        assert!(reached.iter().any(|node| node.identifier() == "Test.Pickup"));
+        let (_, progressions) = graph.reached_and_progressions(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+        assert!(progressions.is_empty());
+
+        world.player.inventory.remove(&Item::Resource(Resource::Health), 2);
+        let reached = graph.reached_locations(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+        assert!(reached.is_empty());
+    }
+
+    #[test]
+    fn resume_improved_orbs() {
+        // the detour to the ledge needs an item, so it only leads there with more health once the check resumes
+        let areas = "anchor Test.Start at 0, 0:\n  conn Test.Ledge:\n    moki: Damage=20\n  conn Test.Detour: free\n\nanchor Test.Detour at 0, 0:\n  conn Test.Ledge:\n    moki: DoubleJump\n\nanchor Test.Ledge at 0, 0:\n  pickup Test.Pickup:\n    moki: Damage=20\n";
+        let locations = "Test.Pickup, Inkwater Marsh, Resource, Life, swampStateGroup, 21786, healthContainerA, 60210, -958, -4313\n";
+
+        let (tokens, metadata) = logic::tokenizer::tokenize(areas).unwrap();
+        let areas = logic::parser::parse_areas(tokens, &metadata).unwrap();
+        let locations = logic::parse_locations(locations).unwrap();
+        let settings = Settings::default();
+        let graph = logic::emitter::emit(&areas, &metadata, &locations, &[], &settings, false).unwrap();
+
+        let mut world = World::new(&graph);
+        world.player.spawn(&settings);
+        let spawn = graph.find_spawn("Test.Start").unwrap();
+
+        let mut reach_state = None;
+        let (reached, progressions) = graph.resume_reach(&world.player, spawn, &world.uber_states, &world.sets, &mut reach_state).unwrap();
+        assert!(!reached.iter().any(|node| node.identifier() == "Test.Pickup"));
+        assert!(progressions.iter().any(|(requirement, _)| matches!(requirement, Requirement::Skill(Skill::DoubleJump))));
+
+        world.player.inventory.grant(Item::Skill(Skill::DoubleJump), 1);
+        let (reached, _) = graph.resume_reach(&world.player, spawn, &world.uber_states, &world.sets, &mut reach_state).unwrap();
+        assert!(reached.iter().any(|node| node.identifier() == "Test.Pickup"));
+    }
+
+    #[test]
+    fn resume_lowered_orb_costs() {
+        // resilience makes every hit cheaper, so the pickup is only affordable if the whole way there is checked again
+        let areas = "anchor Test.Start at 0, 0:\n  conn Test.Middle:\n    gorlek: Damage=10\n\nanchor Test.Middle at 0, 0:\n  conn Test.Ledge:\n    gorlek: Damage=10\n\nanchor Test.Ledge at 0, 0:\n  pickup Test.Pickup:\n    gorlek: Damage=12\n";
+        let locations = "Test.Pickup, Inkwater Marsh, Resource, Life, swampStateGroup, 21786, healthContainerA, 60210, -958, -4313\n";
+
+        let (tokens, metadata) = logic::tokenizer::tokenize(areas).unwrap();
+        let areas = logic::parser::parse_areas(tokens, &metadata).unwrap();
+        let locations = logic::parse_locations(locations).unwrap();
+        let settings = Settings { difficulty: String::from("gorlek"), ..Settings::default() };
+        let graph = logic::emitter::emit(&areas, &metadata, &locations, &[], &settings, false).unwrap();
+
+        let mut world = World::new(&graph);
+        world.player.spawn(&settings);
+        let spawn = graph.find_spawn("Test.Start").unwrap();
+
+        let mut reach_state = None;
+        let (reached, _) = graph.resume_reach(&world.player, spawn, &world.uber_states, &world.sets, &mut reach_state).unwrap();
+        assert!(!reached.iter().any(|node| node.identifier() == "Test.Pickup"));
+
+        world.player.inventory.grant(Item::Shard(Shard::Resilience), 1);
+        let (reached, _) = graph.resume_reach(&world.player, spawn, &world.uber_states, &world.sets, &mut reach_state).unwrap();
+        let (full_reached, _) = graph.reached_and_progressions(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+        assert!(reached.iter().any(|node| node.identifier() == "Test.Pickup"));
+        assert_eq!(reached.len(), full_reached.len());
+    }
+
+    #[test]
+    fn custom_difficulty() {
+        let areas = "# tiers\ndifficulties moki, gorlek, gorlekplus, kii, unsafe\n\nanchor Test.Start at 0, 0:\n  pickup Test.Pickup:\n    gorlekplus: free\n";
+        let locations = "Test.Pickup, Inkwater Marsh, Resource, Life, swampStateGroup, 21786, healthContainerA, 60210, -958, -4313\n";
+
+        let (tokens, metadata) = logic::tokenizer::tokenize(areas).unwrap();
+        assert_eq!(metadata.base_difficulty("gorlekplus"), Some(Difficulty::Gorlek));
+        let areas = logic::parser::parse_areas(tokens, &metadata).unwrap();
+        let locations = logic::parse_locations(locations).unwrap();
+
+        let reaches_pickup = |difficulty: &str| {
+            let settings = Settings { difficulty: difficulty.to_string(), ..Settings::default() };
+            let graph = logic::emitter::emit(&areas, &metadata, &locations, &[], &settings, false)?;
+            let mut world = World::new(&graph);
+            world.player.spawn(&settings);
+            let spawn = graph.find_spawn("Test.Start").unwrap();
+            let reached = graph.reached_locations(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+            Ok::<_, String>((!reached.is_empty(), world.player.difficulty))
+        };
+        assert_eq!(reaches_pickup("gorlek"), Ok((false, Difficulty::Gorlek)));
+        assert_eq!(reaches_pickup("gorlekplus"), Ok((true, Difficulty::Gorlek)));
+        assert_eq!(reaches_pickup("GorlekPlus"), Ok((true, Difficulty::Gorlek)));
+        assert_eq!(reaches_pickup("kii"), Ok((true, Difficulty::Kii)));
+        assert!(reaches_pickup("kiiplus").is_err());
+
+        assert!(logic::tokenizer::tokenize("difficulties moki, kii, gorlek, unsafe\n").is_err());
+        assert!(logic::tokenizer::tokenize("anchor Test.Start at 0, 0:\n  pickup Test.Pickup: free\ndifficulties moki, gorlek, kii, unsafe\n").is_err());
+    }
 }
@@ -1,28 +1,41 @@
+mod export;
+mod explain;
+mod route;
+
 use std::fmt;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 use smallvec::{SmallVec, smallvec};
 
+use rand::{Rng, seq::SliceRandom};
+
 use super::{player::Player, requirements::Requirement};
+use crate::inventory::Inventory;
+use crate::item::{Item, Teleporter};
 use crate::util::{
-    RefillType, NodeType, Position, Zone, UberState, UberIdentifier,
+    Difficulty, RefillType, NodeType, Position, Zone, UberState, UberIdentifier,
     orbs::{self, Orbs},
-    constants::TP_ANCHOR,
+    constants::{TP_ANCHOR, KEYSTONE_DOORS},
 };
 
-#[derive(Debug)]
+pub use export::ExportFormat;
+pub use explain::BlockedConnection;
+pub use route::RouteStep;
+
+#[derive(Debug, Clone)]
 pub struct Refill {
     pub name: RefillType,
     pub requirement: Requirement,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Connection {
     pub to: usize,
     pub requirement: Requirement,
+    pub door: Option<i32>,  // the randomizer's id for the door this connection goes through
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Anchor {
     pub identifier: String,
     pub position: Option<Position>,
@@ -31,7 +44,7 @@ pub struct Anchor {
     pub refills: Vec<Refill>,
     pub connections: Vec<Connection>,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Pickup {
     pub identifier: String,
     pub position: Position,
@@ -39,13 +52,13 @@ pub struct Pickup {
     pub index: usize,
     pub uber_state: UberState,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct State {
     pub identifier: String,
     pub index: usize,
     pub uber_state: Option<UberState>,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Quest {
     pub identifier: String,
     pub position: Position,
@@ -54,7 +67,7 @@ pub struct Quest {
     pub uber_state: UberState,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Node {
     Anchor(Anchor),
     Pickup(Pickup),
@@ -126,6 +139,8 @@ impl fmt::Display for Node {
 
 pub type Reached<'a> = Vec<&'a Node>;
 pub type Progressions<'a> = Vec<(&'a Requirement, SmallVec<[Orbs; 3]>)>;
+// connections that failed on items or orbs, by their anchor and position in it
+type Blocked<'a> = FxHashMap<(usize, usize), (&'a Connection, SmallVec<[Orbs; 3]>)>;
 
 #[derive(Debug)]
 struct ReachContext<'a, 'b> {
@@ -133,32 +148,91 @@ struct ReachContext<'a, 'b> {
     progression_check: bool,
     states: FxHashSet<usize>,
     state_progressions: FxHashMap<usize, Vec<(usize, &'a Connection)>>,
-    world_state: FxHashMap<usize, SmallVec<[Orbs; 3]>>
+    world_state: FxHashMap<usize, SmallVec<[Orbs; 3]>>,
+    blocked: Blocked<'a>,  // only tracked during progression checks
+}
+impl<'a, 'b> ReachContext<'a, 'b> {
+    fn new(player: &'b Player, progression_check: bool, states: FxHashSet<usize>) -> ReachContext<'a, 'b> {
+        ReachContext {
+            player,
+            progression_check,
+            states,
+            state_progressions: FxHashMap::default(),
+            world_state: FxHashMap::default(),
+            blocked: FxHashMap::default(),
+        }
+    }
+}
+
+/// The outcome of a progression check, kept to continue from it once the player found more items
+#[derive(Debug, Clone)]
+pub struct ReachState<'a> {
+    inventory: Inventory,
+    max_orbs: Orbs,
+    extra_states: FxHashSet<usize>,
+    states: FxHashSet<usize>,
+    state_progressions: FxHashMap<usize, Vec<(usize, &'a Connection)>>,
+    world_state: FxHashMap<usize, SmallVec<[Orbs; 3]>>,
+    blocked: Blocked<'a>,
+    reached: Reached<'a>,
+}
+
+/// A shuffled teleporter: unlocking the `access` teleporter allows to teleport to where the `destination` teleporter usually leads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redirect {
+    pub access: Teleporter,
+    pub destination: Teleporter,
+}
+
+/// A shuffled door: going through the `intercept` door leads where the `set` door usually leads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoorRedirect {
+    pub intercept: i32,
+    pub set: i32,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Graph {
     pub nodes: Vec<Node>,
+    /// The builtin difficulty whose game mechanics apply to the logic
+    pub difficulty: Difficulty,
+    pub redirects: Vec<Redirect>,
+    pub door_redirects: Vec<DoorRedirect>,
+    pub keystone_doors: Vec<(usize, u16)>,  // keystone doors with a cost that differs from the logic file
 }
 impl Graph {
-    fn follow_state_progressions<'a>(&'a self, index: usize, context: &mut ReachContext<'a, '_>) -> (Reached<'a>, Progressions<'a>) {
+    fn follow_state_progressions<'a>(&'a self, index: usize, context: &mut ReachContext<'a, '_>) -> Reached<'a> {
         let mut reached = Vec::new();
-        let mut progressions = Vec::new();
         if let Some(connections) = context.state_progressions.get(&index) {
             for (from, connection) in connections.clone() {
-                if context.world_state.contains_key(&connection.to) {
-                    // TODO loop with improved orbs?
-                    continue;
-                }
+                if !self.may_visit(connection.to, context) { continue; }
                 let target_orbs = Graph::try_connection(context.player, connection, &context.world_state[&from], &context.states);
                 if !target_orbs.is_empty() {
-                    let (mut child_reached, mut child_progressions) = self.reach_recursion(&self.nodes[connection.to], false, target_orbs, context);
-                    reached.append(&mut child_reached);
-                    progressions.append(&mut child_progressions);
+                    reached.append(&mut self.visit(connection.to, target_orbs, context));
                 }
             }
         }
-        (reached, progressions)
+        reached
+    }
+    // nodes are visited again only if they are anchors, where better orbs could open up more connections
+    fn may_visit(&self, index: usize, context: &ReachContext) -> bool {
+        context.world_state.get(&index).map_or(true, |prior| matches!(self.nodes[index], Node::Anchor(_)) && !prior.contains(&context.player.max_orbs()))
+    }
+    fn visit<'a>(&'a self, index: usize, target_orbs: SmallVec<[Orbs; 3]>, context: &mut ReachContext<'a, '_>) -> Reached<'a> {
+        match context.world_state.get(&index) {
+            None => self.reach_recursion(&self.nodes[index], false, target_orbs, context),
+            Some(prior) if orbs::improves(prior, &target_orbs) => {
+                let best_orbs = orbs::union(prior, &target_orbs);
+                self.reach_recursion(&self.nodes[index], false, best_orbs, context)
+            },
+            Some(_) => Vec::new(),
+        }
+    }
+    // this anchor may be visited again with better orbs, so the blocked connection keeps the best of them
+    fn block<'a>(blocked: &mut Blocked<'a>, key: (usize, usize), connection: &'a Connection, best_orbs: &[Orbs]) {
+        blocked.entry(key)
+            .and_modify(|(_, blocked_orbs)| *blocked_orbs = orbs::union(blocked_orbs, best_orbs))
+            .or_insert_with(|| (connection, best_orbs.iter().copied().collect()));
     }
     fn try_connection(player: &Player, connection: &Connection, best_orbs: &[Orbs], states: &FxHashSet<usize>) -> SmallVec<[Orbs; 3]> {
         let mut target_orbs = SmallVec::<[Orbs; 3]>::default();
@@ -170,40 +244,41 @@ impl Graph {
         target_orbs
     }
 
-    fn reach_recursion<'a>(&'a self, entry: &'a Node, is_spawn: bool, mut best_orbs: SmallVec<[Orbs; 3]>, context: &mut ReachContext<'a, '_>) -> (Reached<'a>, Progressions<'a>) {
-        context.world_state.insert(entry.index(), best_orbs.clone());
-        match entry {
-            Node::Anchor(anchor) => {
-                let max_orbs = context.player.max_orbs();
-                if best_orbs.get(0).map_or(true, |first_orbs| first_orbs != &max_orbs) {
-                    for refill in &anchor.refills {
-                        for orbs in &best_orbs {
-                            if let Some(orbcost) = refill.requirement.is_met(context.player, &context.states, *orbs) {
-                                if matches!(refill.name, RefillType::Full) {
-                                    best_orbs = smallvec![max_orbs];
-                                    break;
-                                }
-                                let mut refill_orbs = orbs::both(&best_orbs, &orbcost);
-                                match refill.name {
-                                    RefillType::Checkpoint => refill_orbs = context.player.checkpoint_orbs(&refill_orbs),
-                                    RefillType::Health(amount) => refill_orbs = context.player.health_orbs(&refill_orbs, amount),
-                                    RefillType::Energy(amount) => refill_orbs = context.player.energy_orbs(&refill_orbs, amount),
-                                    RefillType::Full => unreachable!(),
-                                }
-                                best_orbs = orbs::either(&best_orbs, &refill_orbs);
-                                break;
-                            }
+    fn apply_refills(player: &Player, anchor: &Anchor, mut best_orbs: SmallVec<[Orbs; 3]>, states: &FxHashSet<usize>) -> SmallVec<[Orbs; 3]> {
+        let max_orbs = player.max_orbs();
+        if best_orbs.get(0).map_or(true, |first_orbs| first_orbs != &max_orbs) {
+            for refill in &anchor.refills {
+                for orbs in &best_orbs {
+                    if let Some(orbcost) = refill.requirement.is_met(player, states, *orbs) {
+                        if matches!(refill.name, RefillType::Full) {
+                            best_orbs = smallvec![max_orbs];
+                            break;
                         }
+                        let mut refill_orbs = orbs::both(&best_orbs, &orbcost);
+                        match refill.name {
+                            RefillType::Checkpoint => refill_orbs = player.checkpoint_orbs(&refill_orbs),
+                            RefillType::Health(amount) => refill_orbs = player.health_orbs(&refill_orbs, amount),
+                            RefillType::Energy(amount) => refill_orbs = player.energy_orbs(&refill_orbs, amount),
+                            RefillType::Full => unreachable!(),
+                        }
+                        best_orbs = orbs::either(&best_orbs, &refill_orbs);
+                        break;
                     }
                 }
+            }
+        }
+        best_orbs
+    }
+
+    fn reach_recursion<'a>(&'a self, entry: &'a Node, is_spawn: bool, mut best_orbs: SmallVec<[Orbs; 3]>, context: &mut ReachContext<'a, '_>) -> Reached<'a> {
+        context.world_state.insert(entry.index(), best_orbs.clone());
+        match entry {
+            Node::Anchor(anchor) => {
+                best_orbs = Graph::apply_refills(context.player, anchor, best_orbs, &context.states);
 
                 let mut reached = Vec::new();
-                let mut progressions = Vec::new();
-                for connection in &anchor.connections {
-                    if context.world_state.contains_key(&connection.to) {
-                        // TODO loop with improved orbs?
-                        continue;
-                    }
+                for (connection_index, connection) in anchor.connections.iter().enumerate() {
+                    if !self.may_visit(connection.to, context) { continue; }
                     let target_orbs = Graph::try_connection(context.player, connection, &best_orbs, &context.states);
                     if target_orbs.is_empty() {
                         let mut states = connection.requirement.contained_states();
@@ -211,42 +286,45 @@ impl Graph {
 
                         if states.is_empty() {
                             if context.progression_check {
-                                progressions.push((&connection.requirement, best_orbs.clone()))
+                                // connections to anchors that were already reached count too, they might lead there with better orbs
+                                Graph::block(&mut context.blocked, (anchor.index, connection_index), connection, &best_orbs);
                             }
                         } else {
                             for state in states {
-                                context.state_progressions.entry(state).or_default().push((anchor.index, connection));
+                                let state_progressions = context.state_progressions.entry(state).or_default();
+                                if !state_progressions.iter().any(|&(_, waiting)| std::ptr::eq(waiting, connection)) {
+                                    state_progressions.push((anchor.index, connection));
+                                }
                             }
                         }
                     } else {
-                        let (mut child_reached, mut child_progressions) = self.reach_recursion(&self.nodes[connection.to], false, target_orbs, context);
-                        reached.append(&mut child_reached);
-                        progressions.append(&mut child_progressions);
+                        if context.progression_check {
+                            context.blocked.remove(&(anchor.index, connection_index));
+                        }
+                        reached.append(&mut self.visit(connection.to, target_orbs, context));
                     }
                 }
                 if is_spawn {
                     if let Some(tp_anchor) = self.nodes.iter().find(|&node| node.identifier() == TP_ANCHOR) {
                         if !anchor.connections.iter().any(|connection| connection.to == tp_anchor.index()) {
-                            let (mut tp_reached, mut tp_progressions) = self.reach_recursion(tp_anchor, false, best_orbs, context);
-                            reached.append(&mut tp_reached);
-                            progressions.append(&mut tp_progressions);
+                            reached.append(&mut self.reach_recursion(tp_anchor, false, best_orbs, context));
                         }
                     }
                 }
-                (reached, progressions)
+                reached
             },
-            Node::Pickup(_) => (vec![entry], vec![]),
+            Node::Pickup(_) => vec![entry],
             Node::State(state) => {
                 context.states.insert(state.index);
-                let (mut reached, progressions) = self.follow_state_progressions(state.index, context);
+                let mut reached = self.follow_state_progressions(state.index, context);
                 reached.push(entry);
-                (reached, progressions)
+                reached
             },
             Node::Quest(quest) => {
                 context.states.insert(quest.index);
-                let (mut reached, progressions) = self.follow_state_progressions(quest.index, context);
+                let mut reached = self.follow_state_progressions(quest.index, context);
                 reached.push(entry);
-                (reached, progressions)
+                reached
             },
         }
     }
@@ -286,38 +364,258 @@ impl Graph {
     }
 
     pub fn reached_locations<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<Reached<'a>, String> {
-        let mut context = ReachContext {
-            player,
-            progression_check: false,
-            states: self.collect_extra_states(extra_states, sets),
-            state_progressions: FxHashMap::default(),
-            world_state: FxHashMap::default(),
-        };
+        let mut context = ReachContext::new(player, false, self.collect_extra_states(extra_states, sets));
 
-        let (reached, _) = self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context);
+        let reached = self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context);
 
         Ok(reached)
     }
+    /// Every anchor that can be reached, which `reached_locations` leaves out
+    pub fn reached_anchors<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Vec<&'a Node> {
+        let mut context = ReachContext::new(player, false, self.collect_extra_states(extra_states, sets));
+
+        self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context);
+
+        let mut reached = context.world_state.keys()
+            .map(|&index| &self.nodes[index])
+            .filter(|node| matches!(node, Node::Anchor(_)))
+            .collect::<Vec<_>>();
+        reached.sort_unstable_by_key(|node| node.index());
+        reached
+    }
     pub fn reached_and_progressions<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<(Reached<'a>, Progressions<'a>), String> {
-        let mut context = ReachContext {
-            player,
-            progression_check: true,
-            states: self.collect_extra_states(extra_states, sets),
-            state_progressions: FxHashMap::default(),
-            world_state: FxHashMap::default(),
-        };
+        let mut reach_state = None;
+        self.resume_reach(player, spawn, extra_states, sets, &mut reach_state)
+    }
+    /// Like `reached_and_progressions`, but continues from the previous check stored in `reach_state` instead of walking the whole graph again
+    ///
+    /// Only the connections that were blocked last time are checked again. If the player lost any items or states since, their maximum health or energy changed, or they found items that might lower orb costs, everything is checked from scratch instead
+    pub fn resume_reach<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize], reach_state: &mut Option<ReachState<'a>>) -> Result<(Reached<'a>, Progressions<'a>), String> {
+        let current_states = self.collect_extra_states(extra_states, sets);
+
+        let previous = reach_state.take().filter(|previous|
+            previous.max_orbs == player.max_orbs() &&
+            player.inventory.contains(&previous.inventory) &&
+            !player.lowers_orb_costs(&previous.inventory) &&
+            previous.extra_states.is_subset(&current_states)
+        );
+
+        let mut context = ReachContext::new(player, true, current_states.clone());
+        let reached = match previous {
+            Some(previous) => {
+                context.states.extend(previous.states);
+                context.state_progressions = previous.state_progressions;
+                context.world_state = previous.world_state;
+                let mut reached = previous.reached;
+
+                for state in current_states.difference(&previous.extra_states) {
+                    reached.append(&mut self.follow_state_progressions(*state, &mut context));
+                }
+                let mut blocked = previous.blocked.into_iter().collect::<Vec<_>>();
+                blocked.sort_unstable_by_key(|&(key, _)| key);
+                for (key, (connection, best_orbs)) in blocked {
+                    if !self.may_visit(connection.to, &context) { continue; }
+                    let target_orbs = Graph::try_connection(player, connection, &best_orbs, &context.states);
+                    if target_orbs.is_empty() {
+                        Graph::block(&mut context.blocked, key, connection, &best_orbs);
+                    } else {
+                        reached.append(&mut self.visit(connection.to, target_orbs, &mut context));
+                    }
+                }
+                // connections which also need items once their states are met
+                let waiting = context.state_progressions.values().flatten().copied().collect::<Vec<_>>();
+                for (from, connection) in waiting {
+                    if !self.may_visit(connection.to, &context) { continue; }
+                    let target_orbs = Graph::try_connection(player, connection, &context.world_state[&from], &context.states);
+                    if !target_orbs.is_empty() {
+                        reached.append(&mut self.visit(connection.to, target_orbs, &mut context));
+                    }
+                }
 
-        let (reached, mut progressions) = self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context);
+                reached
+            },
+            None => self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context),
+        };
 
+        let mut blocked = context.blocked.iter().filter(|(_, (connection, _))| self.may_visit(connection.to, &context)).collect::<Vec<_>>();
+        blocked.sort_unstable_by_key(|&(key, _)| key);
+        let mut progressions = blocked.into_iter().map(|(_, (connection, best_orbs))| (&connection.requirement, best_orbs.clone())).collect::<Vec<_>>();
         // add progressions containing states that were never met
-        for (_, state_progressions) in context.state_progressions {
+        for state_progressions in context.state_progressions.values() {
             for (from, connection) in state_progressions {
                 if !context.world_state.contains_key(&connection.to) {
-                    progressions.push((&connection.requirement, context.world_state[&from].clone()))
+                    progressions.push((&connection.requirement, context.world_state[from].clone()))
                 }
             }
         }
 
+        *reach_state = Some(ReachState {
+            inventory: player.inventory.clone(),
+            max_orbs: player.max_orbs(),
+            extra_states: current_states,
+            states: context.states,
+            state_progressions: context.state_progressions,
+            world_state: context.world_state,
+            blocked: context.blocked,
+            reached: reached.clone(),
+        });
+
         Ok((reached, progressions))
     }
+
+    /// Find the anchors which can be reached from the spawn, but from which neither the spawn nor an unlocked teleporter can be reached again
+    ///
+    /// The way back is checked assuming full health and energy at every anchor, so traps that only exist due to resource costs may be missed.
+    /// Anchors without connections to other anchors are dead ends where the logic file doesn't describe the way back, these are never considered traps.
+    pub fn traps<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<Vec<&'a Node>, String> {
+        let mut context = ReachContext::new(player, false, self.collect_extra_states(extra_states, sets));
+        self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context);
+
+        let max_orbs = player.max_orbs();
+        let tp_anchor = self.nodes.iter().find(|&node| node.identifier() == TP_ANCHOR).map(Node::index);
+
+        let mut safe = vec![spawn.index()];
+        let mut incoming = FxHashMap::<usize, Vec<usize>>::default();
+        for node in &self.nodes {
+            if let Node::Anchor(anchor) = node {
+                for connection in &anchor.connections {
+                    if connection.requirement.is_met(player, &context.states, max_orbs).is_none() { continue; }
+
+                    if Some(anchor.index) == tp_anchor {
+                        safe.push(connection.to);
+                    } else {
+                        incoming.entry(connection.to).or_default().push(anchor.index);
+                    }
+                }
+            }
+        }
+
+        let mut escapes = FxHashSet::default();
+        while let Some(index) = safe.pop() {
+            if escapes.insert(index) {
+                if let Some(sources) = incoming.get(&index) {
+                    safe.extend(sources.iter().copied());
+                }
+            }
+        }
+
+        let mut traps = context.world_state.keys()
+            .filter(|&&index| Some(index) != tp_anchor && !escapes.contains(&index))
+            .map(|&index| &self.nodes[index])
+            .filter(|&node| matches!(node, Node::Anchor(anchor) if
+                anchor.position.is_some() &&  // anchors without a position are menus or fights rather than places to be stuck in
+                anchor.connections.iter().any(|connection| matches!(self.nodes[connection.to], Node::Anchor(_)))  // dead ends like shrines leave out the way back in the logic file
+            ))
+            .collect::<Vec<_>>();
+        traps.sort_unstable_by_key(|&node| node.index());
+
+        Ok(traps)
+    }
+
+    /// Which teleporter has to be unlocked to teleport where the given teleporter usually leads
+    pub fn teleporter_access(&self, teleporter: Teleporter) -> Teleporter {
+        self.redirects.iter().find(|redirect| redirect.destination == teleporter).map_or(teleporter, |redirect| redirect.access)
+    }
+    /// Where unlocking the given teleporter allows to teleport, named by the teleporter that usually leads there
+    pub fn teleporter_destination(&self, teleporter: Teleporter) -> Teleporter {
+        self.redirects.iter().find(|redirect| redirect.access == teleporter).map_or(teleporter, |redirect| redirect.destination)
+    }
+    /// The item the randomizer has to grant for an item of the logic
+    ///
+    /// The randomizer unlocks teleporters through their items, so a shuffled teleporter item grants the teleporter it leads to instead.
+    pub fn seed_item(&self, item: &Item) -> Item {
+        match item {
+            Item::Teleporter(teleporter) => Item::Teleporter(self.teleporter_destination(*teleporter)),
+            _ => item.clone(),
+        }
+    }
+
+    /// How many keystones it takes to open the given keystone door
+    pub fn keystone_cost(&self, door: &Node) -> Option<u16> {
+        self.keystone_doors.iter().find(|&&(index, _)| index == door.index()).map(|&(_, cost)| cost)
+            .or_else(|| KEYSTONE_DOORS.iter().find(|&&(identifier, ..)| identifier == door.identifier()).map(|&(_, cost, _)| cost))
+    }
+
+    /// Change the keystone requirement of every keystone door to a random amount in the given inclusive range
+    pub fn randomize_keystone_costs<R: Rng>(&mut self, (min, max): (u16, u16), rng: &mut R) -> Result<(), String> {
+        if min > max {
+            return Err(format!("Invalid keystone cost range {}-{}", min, max));
+        }
+
+        self.keystone_doors.clear();
+        for &(identifier, ..) in KEYSTONE_DOORS {
+            let door_index = if let Some(door) = self.nodes.iter().find(|&node| node.identifier() == identifier) { door.index() } else { continue };
+            let cost = rng.gen_range(min..=max);
+
+            for node in &mut self.nodes {
+                if let Node::Anchor(anchor) = node {
+                    for connection in anchor.connections.iter_mut().filter(|connection| connection.to == door_index) {
+                        connection.requirement.replace_keystones(cost);
+                    }
+                }
+            }
+            self.keystone_doors.push((door_index, cost));
+        }
+
+        Ok(())
+    }
+
+    /// Create a copy of the graph with shuffled teleporter destinations
+    ///
+    /// Teleporter destinations are shuffled by exchanging which teleporter is required for each connection of the teleporter anchor, the teleporters in `fixed` keep their destination.
+    /// The resulting [`Redirect`]s are stored on the returned graph.
+    pub fn shuffle_teleporters<R: Rng>(&self, fixed: &[Teleporter], rng: &mut R) -> Result<Graph, String> {
+        let mut graph = self.clone();
+        graph.redirects.clear();
+
+        let tp_anchor = graph.nodes.iter_mut().find_map(|node| match node {
+            Node::Anchor(anchor) if anchor.identifier == TP_ANCHOR => Some(anchor),
+            _ => None,
+        }).ok_or_else(|| format!("Failed to shuffle teleporters: anchor {} not found", TP_ANCHOR))?;
+
+        let shuffled = |connection: &Connection| connection.requirement.contained_teleporter().filter(|teleporter| !fixed.contains(teleporter));
+
+        let mut accesses = tp_anchor.connections.iter().filter_map(shuffled).collect::<Vec<_>>();
+        accesses.shuffle(rng);
+
+        let mut accesses = accesses.into_iter();
+        for connection in &mut tp_anchor.connections {
+            if let Some(destination) = shuffled(connection) {
+                let access = accesses.next().unwrap();
+                if access != destination {
+                    connection.requirement.replace_teleporter(destination, access);
+                    graph.redirects.push(Redirect { access, destination });
+                }
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Create a copy of the graph with shuffled door connections
+    ///
+    /// Door connections, written as `door` with the randomizer's door id instead of `conn` in the logic file, exchange their targets among each other.
+    /// The resulting [`DoorRedirect`]s are stored on the returned graph.
+    pub fn shuffle_doors<R: Rng>(&self, rng: &mut R) -> Graph {
+        let mut graph = self.clone();
+        graph.door_redirects.clear();
+
+        let doors = graph.nodes.iter()
+            .filter_map(|node| if let Node::Anchor(anchor) = node { Some(anchor) } else { None })
+            .flat_map(|anchor| anchor.connections.iter().enumerate()
+                .filter_map(move |(connection_index, connection)| connection.door.map(|door| (anchor.index, connection_index, door, connection.to))))
+            .collect::<Vec<_>>();
+        let mut targets = doors.iter().map(|&(_, _, door, to)| (door, to)).collect::<Vec<_>>();
+        targets.shuffle(rng);
+
+        for (&(anchor_index, connection_index, intercept, vanilla), (set, to)) in doors.iter().zip(targets) {
+            if to == vanilla { continue; }
+            if let Node::Anchor(anchor) = &mut graph.nodes[anchor_index] {
+                anchor.connections[connection_index].to = to;
+            }
+            graph.door_redirects.push(DoorRedirect { intercept, set });
+        }
+
+        graph
+    }
 }
@@ -0,0 +1,100 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use smallvec::smallvec;
+
+use super::{Graph, Node, ReachContext};
+use crate::inventory::Inventory;
+use crate::world::{player::Player, requirements::Requirement};
+use crate::util::UberIdentifier;
+
+/// A connection out of the reachable part of the logic that would lead closer to the node in question
+#[derive(Debug)]
+pub struct BlockedConnection<'a> {
+    pub from: &'a Node,
+    pub to: &'a Node,
+    pub requirement: &'a Requirement,
+    /// The cheapest sets of items that would unlock the connection, empty if it depends on states that can't be reached yet
+    pub unlocks: Vec<Inventory>,
+}
+
+impl Graph {
+    // every node that has a path to the target if all requirements were ignored
+    fn leads_to(&self, target: &Node) -> FxHashSet<usize> {
+        let mut incoming = FxHashMap::<usize, Vec<usize>>::default();
+        for node in &self.nodes {
+            if let Node::Anchor(anchor) = node {
+                for connection in &anchor.connections {
+                    incoming.entry(connection.to).or_default().push(anchor.index);
+                }
+            }
+        }
+
+        let mut leads_to = FxHashSet::default();
+        let mut queue = vec![target.index()];
+        while let Some(index) = queue.pop() {
+            if leads_to.insert(index) {
+                if let Some(sources) = incoming.get(&index) {
+                    queue.extend(sources.iter().copied());
+                }
+            }
+        }
+        leads_to
+    }
+
+    /// Explain why the target can't be reached, returns `None` if it can
+    ///
+    /// Lists the blocked connections from reachable anchors that have a path to the target, along with the cheapest itemsets that would unlock each of them
+    pub fn explain_unreachable<'a>(&'a self, player: &Player, spawn: &'a Node, target: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize], max_unlocks: usize) -> Option<Vec<BlockedConnection<'a>>> {
+        let mut context = ReachContext::new(player, false, self.collect_extra_states(extra_states, sets));
+        self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context);
+
+        if context.world_state.contains_key(&target.index()) {
+            return None;
+        }
+
+        let leads_to = self.leads_to(target);
+        let owned_states = context.states.iter().copied().collect::<Vec<_>>();
+
+        let mut reached = context.world_state.keys().copied().collect::<Vec<_>>();
+        reached.sort_unstable();
+
+        let mut blocked = Vec::new();
+        for index in reached {
+            if let Node::Anchor(anchor) = &self.nodes[index] {
+                for connection in &anchor.connections {
+                    if context.world_state.contains_key(&connection.to) || !leads_to.contains(&connection.to) { continue; }
+
+                    let mut unlocks: Vec<Inventory> = Vec::new();
+                    for (mut needed, orb_cost) in connection.requirement.items_needed(player, &owned_states) {
+                        player.missing_items(&mut needed);
+                        for orbs in &context.world_state[&index] {
+                            for missing in player.missing_for_orbs(&needed, orb_cost, *orbs) {
+                                if !missing.is_empty() && !unlocks.iter().any(|other| missing.contains(other)) {
+                                    unlocks.retain(|other| !other.contains(&missing));
+                                    unlocks.push(missing);
+                                }
+                            }
+                        }
+                    }
+                    unlocks.sort_by(|a, b| a.cost().partial_cmp(&b.cost()).unwrap_or(std::cmp::Ordering::Equal));
+                    unlocks.truncate(max_unlocks);
+
+                    blocked.push(BlockedConnection {
+                        from: &self.nodes[index],
+                        to: &self.nodes[connection.to],
+                        requirement: &connection.requirement,
+                        unlocks,
+                    });
+                }
+            }
+        }
+
+        // connections right into the target first, then the cheapest way forward, connections that need more progress elsewhere last
+        blocked.sort_by(|a, b| {
+            let cost = |blocked: &BlockedConnection| blocked.unlocks.first().map_or(f32::INFINITY, Inventory::cost);
+            (a.to.index() != target.index()).cmp(&(b.to.index() != target.index()))
+                .then_with(|| cost(a).partial_cmp(&cost(b)).unwrap_or(std::cmp::Ordering::Equal))
+        });
+
+        Some(blocked)
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write;
+
+use rustc_hash::FxHashMap;
+
+use super::{Graph, Node};
+use crate::world::requirements::Requirement;
+use crate::util::Zone;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Dot,
+    GraphML,
+}
+
+impl Graph {
+    /// Render a requirement on a single line in the logic file's notation, with parentheses where the logic file would nest an indented block
+    pub fn display_requirement(&self, requirement: &Requirement) -> String {
+        match requirement {
+            Requirement::Free => String::from("free"),
+            Requirement::Impossible => String::from("Impossible"),
+            Requirement::Skill(skill) | Requirement::NonConsumingEnergySkill(skill) => format!("{:?}", skill),
+            Requirement::EnergySkill(skill, amount) => format!("{:?}={}", skill, amount),
+            Requirement::SpiritLight(amount) => format!("SpiritLight={}", amount),
+            Requirement::Resource(resource, amount) => format!("{:?}={}", resource, amount),
+            Requirement::Shard(shard) => format!("{:?}", shard),
+            Requirement::Teleporter(teleporter) => format!("{:?}TP", teleporter),
+            Requirement::Water => String::from("Water"),
+            Requirement::State(index) => self.nodes[*index].identifier().to_string(),
+            Requirement::Damage(amount) => format!("Damage={}", amount),
+            Requirement::Danger(amount) => format!("Danger={}", amount),
+            Requirement::Combat(enemies) => {
+                let enemies = enemies.iter()
+                    .map(|(enemy, amount)| if *amount > 1 { format!("{}x{:?}", amount, enemy) } else { format!("{:?}", enemy) })
+                    .collect::<Vec<_>>();
+                format!("Combat={}", enemies.join("+"))
+            },
+            Requirement::Boss(health) => format!("Boss={}", health),
+            Requirement::BreakWall(health) => format!("BreakWall={}", health),
+            Requirement::ShurikenBreak(health) => format!("ShurikenBreak={}", health),
+            Requirement::SentryBreak(health) => format!("SentryBreak={}", health),
+            Requirement::And(requirements) => requirements.iter().map(|requirement| self.display_requirement(requirement)).collect::<Vec<_>>().join(", "),
+            Requirement::Or(requirements) => requirements.iter()
+                .map(|requirement| match requirement {
+                    // OR binds stronger than the comma
+                    Requirement::And(_) => format!("({})", self.display_requirement(requirement)),
+                    _ => self.display_requirement(requirement),
+                })
+                .collect::<Vec<_>>().join(" OR "),
+        }
+    }
+
+    // Anchors don't belong to a zone, so they take the zone most of their pickups are in, or otherwise the zone of an anchor next to them
+    fn anchor_zones(&self) -> Vec<Option<Zone>> {
+        let mut zones = self.nodes.iter().map(|node| match node {
+            Node::Anchor(anchor) => {
+                let mut counts = FxHashMap::<Zone, usize>::default();
+                for connection in &anchor.connections {
+                    if let Some(zone) = self.nodes[connection.to].zone() {
+                        *counts.entry(zone).or_default() += 1;
+                    }
+                }
+                counts.into_iter().max_by_key(|&(zone, count)| (count, zone as u8)).map(|(zone, _)| zone)
+            },
+            _ => node.zone(),
+        }).collect::<Vec<_>>();
+
+        loop {
+            let mut changed = false;
+            for node in &self.nodes {
+                if let Node::Anchor(anchor) = node {
+                    if zones[anchor.index].is_some() { continue; }
+                    let neighbour = anchor.connections.iter().find_map(|connection| match &self.nodes[connection.to] {
+                        Node::Anchor(_) => zones[connection.to],
+                        _ => None,
+                    });
+                    if neighbour.is_some() {
+                        zones[anchor.index] = neighbour;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed { break; }
+        }
+
+        zones
+    }
+
+    /// Export the logic for visualization, limited to the given zones if any are specified
+    ///
+    /// Nodes outside of the zones are still included if a connection from inside the zones leads to them
+    pub fn export(&self, format: ExportFormat, zones: &[Zone]) -> String {
+        let anchor_zones = self.anchor_zones();
+        let in_zones = |node: &Node| zones.is_empty() || anchor_zones[node.index()].map_or(false, |zone| zones.contains(&zone));
+
+        let mut included = vec![false; self.nodes.len()];
+        let mut edges = Vec::new();
+        for node in &self.nodes {
+            if let Node::Anchor(anchor) = node {
+                if !in_zones(node) { continue; }
+                included[anchor.index] = true;
+                for connection in &anchor.connections {
+                    included[connection.to] = true;
+                    edges.push((anchor.index, connection.to, self.display_requirement(&connection.requirement)));
+                }
+            }
+        }
+        let nodes = self.nodes.iter().filter(|node| included[node.index()]).map(|node| (node, !in_zones(node), anchor_zones[node.index()]));
+
+        let mut output = String::new();
+        match format {
+            ExportFormat::Dot => {
+                output.push_str("digraph logic {\n");
+                for (node, outside, _) in nodes {
+                    let shape = match node {
+                        Node::Anchor(_) => "box",
+                        Node::Pickup(_) => "ellipse",
+                        Node::State(_) => "diamond",
+                        Node::Quest(_) => "hexagon",
+                    };
+                    let style = if outside { ", style=dashed" } else { "" };
+                    writeln!(output, "  \"{}\" [shape={}{}];", escape_dot(node.identifier()), shape, style).unwrap();
+                }
+                for (from, to, requirement) in edges {
+                    writeln!(output, "  \"{}\" -> \"{}\" [label=\"{}\"];", escape_dot(self.nodes[from].identifier()), escape_dot(self.nodes[to].identifier()), escape_dot(&requirement)).unwrap();
+                }
+                output.push_str("}\n");
+            },
+            ExportFormat::GraphML => {
+                output.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+                output.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+                output.push_str("  <key id=\"type\" for=\"node\" attr.name=\"type\" attr.type=\"string\"/>\n");
+                output.push_str("  <key id=\"zone\" for=\"node\" attr.name=\"zone\" attr.type=\"string\"/>\n");
+                output.push_str("  <key id=\"requirement\" for=\"edge\" attr.name=\"requirement\" attr.type=\"string\"/>\n");
+                output.push_str("  <graph id=\"logic\" edgedefault=\"directed\">\n");
+                for (node, _, zone) in nodes {
+                    writeln!(output, "    <node id=\"{}\">", escape_xml(node.identifier())).unwrap();
+                    writeln!(output, "      <data key=\"type\">{:?}</data>", node.node_type()).unwrap();
+                    if let Some(zone) = zone {
+                        writeln!(output, "      <data key=\"zone\">{:?}</data>", zone).unwrap();
+                    }
+                    output.push_str("    </node>\n");
+                }
+                for (from, to, requirement) in edges {
+                    writeln!(output, "    <edge source=\"{}\" target=\"{}\">", escape_xml(self.nodes[from].identifier()), escape_xml(self.nodes[to].identifier())).unwrap();
+                    writeln!(output, "      <data key=\"requirement\">{}</data>", escape_xml(&requirement)).unwrap();
+                    output.push_str("    </edge>\n");
+                }
+                output.push_str("  </graph>\n</graphml>\n");
+            },
+        }
+
+        output
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+
+use rustc_hash::FxHashMap;
+use smallvec::{SmallVec, smallvec};
+
+use super::{Graph, Node, ReachContext};
+use crate::world::{player::Player, requirements::Requirement};
+use crate::util::{UberIdentifier, constants::TP_ANCHOR, orbs::{self, Orbs}};
+
+/// One node along a route, together with how it was entered
+#[derive(Debug)]
+pub struct RouteStep<'a> {
+    pub node: &'a Node,
+    /// The requirement of the connection used to get here, `None` for the start and for teleporting
+    pub requirement: Option<&'a Requirement>,
+    /// The possible health and energy after arriving here, including any refills
+    pub orbs: SmallVec<[Orbs; 3]>,
+}
+
+impl Graph {
+    /// Find the route with the fewest connections from the start to the target
+    ///
+    /// Teleporting is available from the start like it is from the spawn. States that can be reached from the start are assumed to be met already
+    ///
+    /// Nodes are visited again when they're reached with better orbs, so a longer route is found if the shorter ones run out of health or energy
+    pub fn find_route<'a>(&'a self, player: &Player, start: &'a Node, target: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Option<Vec<RouteStep<'a>>> {
+        let mut context = ReachContext::new(player, false, self.collect_extra_states(extra_states, sets));
+        self.reach_recursion(start, true, smallvec![player.max_orbs()], &mut context);
+        if !context.world_state.contains_key(&target.index()) {
+            return None;
+        }
+        let states = context.states;
+
+        let tp_anchor = self.nodes.iter().find(|&node| node.identifier() == TP_ANCHOR);
+
+        // every time a node was reached with orbs that are better in some way than before, together with the step it was reached from
+        let mut steps = vec![(start.index(), smallvec![player.max_orbs()], None)];
+        let mut best_orbs = FxHashMap::<usize, SmallVec<[Orbs; 3]>>::default();
+        best_orbs.insert(start.index(), smallvec![player.max_orbs()]);
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+
+        let mut found = None;
+        while let Some(step) = queue.pop_front() {
+            let index = steps[step].0;
+            if index == target.index() && !matches!(target, Node::Anchor(_)) {
+                found = Some(step);
+                break;
+            }
+            if let Node::Anchor(anchor) = &self.nodes[index] {
+                let orbs = Graph::apply_refills(player, anchor, steps[step].1.clone(), &states);
+                steps[step].1 = orbs.clone();
+                if index == target.index() {
+                    found = Some(step);
+                    break;
+                }
+
+                let mut next = anchor.connections.iter().filter_map(|connection| {
+                    let target_orbs = Graph::try_connection(player, connection, &orbs, &states);
+                    if target_orbs.is_empty() { None } else { Some((connection.to, target_orbs, Some(&connection.requirement))) }
+                }).collect::<Vec<_>>();
+                if step == 0 {
+                    if let Some(tp_anchor) = tp_anchor {
+                        next.push((tp_anchor.index(), orbs, None));
+                    }
+                }
+
+                for (to, target_orbs, requirement) in next {
+                    let best_orbs = best_orbs.entry(to).or_default();
+                    if orbs::improves(best_orbs, &target_orbs) {
+                        *best_orbs = orbs::union(best_orbs, &target_orbs);
+                        steps.push((to, target_orbs, Some((step, requirement))));
+                        queue.push_back(steps.len() - 1);
+                    }
+                }
+            }
+        }
+
+        let mut route = Vec::new();
+        let mut current = Some(found?);
+        while let Some(step) = current {
+            let (index, orbs, parent) = steps[step].clone();
+            route.push(RouteStep { node: &self.nodes[index], requirement: parent.and_then(|(_, requirement)| requirement), orbs });
+            current = parent.map(|(step, _)| step);
+        }
+        route.reverse();
+
+        Some(route)
+    }
+}
@@ -23,7 +23,6 @@ impl Player {
     }
 
     pub fn apply_settings(&mut self, settings: &Settings) {
-        self.difficulty = settings.difficulty;
         self.hard = settings.hard;
     }
 
@@ -149,6 +148,30 @@ impl Player {
         energy_mod
     }
 
+    /// Whether the items gained since `previous` might make orb costs in the logic cheaper
+    pub fn lowers_orb_costs(&self, previous: &Inventory) -> bool {
+        self.inventory.iter().any(|(item, amount)| match item {
+            Item::Skill(
+                Skill::Sword |
+                Skill::Hammer |
+                Skill::Bow |
+                Skill::Grenade |
+                Skill::Shuriken |
+                Skill::Blaze |
+                Skill::Spear |
+                Skill::Flash |
+                Skill::Sentry |
+                Skill::Launch |
+                Skill::AncestralLight |
+                Skill::Regenerate |
+                Skill::Burrow
+            ) |
+            Item::Shard(_) | Item::Resource(Resource::ShardSlot) => amount > previous.get(&item),
+            Item::SpiritLight(_) => self.inventory.has(&Item::Shard(Shard::SpiritSurge), 1) && amount > previous.get(&item),
+            _ => false,
+        })
+    }
+
     pub fn use_cost(&self, skill: Skill) -> f32 {
         skill.energy_cost() * self.energy_mod()
     }
@@ -250,12 +273,10 @@ impl Player {
     }
 
     pub fn missing_items(&self, needed: &mut Inventory) {
-        for (item, amount) in &mut needed.items {
-            let owned = self.inventory.get(item);
-            *amount -= owned.min(*amount);
+        let owned = needed.iter().map(|(item, amount)| (amount.min(self.inventory.get(&item)), item)).collect::<Vec<_>>();
+        for (amount, item) in owned {
+            needed.remove(&item, amount);
         }
-
-        needed.items.retain(|_, amount| *amount > 0);
     }
     pub fn missing_for_orbs(&self, needed: &Inventory, orb_cost: Orbs, current_orbs: Orbs) -> Vec<Inventory> {
         let orbs = current_orbs + orb_cost;
@@ -310,7 +331,7 @@ impl Player {
         }
 
         for inventory in &mut inventories {
-            for (item, amount) in needed.items.clone() {
+            for (item, amount) in needed.iter() {
                 inventory.grant(item, amount);
             }
         }
@@ -323,7 +344,7 @@ impl Player {
 mod tests {
     use super::*;
 
-    use crate::item::BonusItem;
+    use crate::item::{BonusItem, Teleporter};
     use crate::util::*;
 
     #[test]
@@ -335,6 +356,28 @@ mod tests {
         assert!(player.inventory.has(&Item::BonusItem(BonusItem::Relic), 2));
         assert!(player.inventory.has(&Item::Skill(Skill::Shuriken), 1));
         assert!(!player.inventory.has(&Item::Skill(Skill::Bash), 0));
+
+        player.inventory.grant(Item::SpiritLight(50), 3);
+        player.inventory.grant(Item::Teleporter(Teleporter::Shriek), 1);
+        player.inventory.grant(Item::Shard(Shard::Arcing), 1);
+        player.inventory.grant(Item::Skill(Skill::AncestralLight), 2);
+        assert_eq!(player.inventory.get(&Item::SpiritLight(1)), 150);
+        assert_eq!(player.inventory.get(&Item::Skill(Skill::AncestralLight)), 2);
+        assert_eq!(player.inventory.item_count(), 11);
+
+        let other = Inventory::from(vec![(Item::Skill(Skill::Shuriken), 1), (Item::Water, 1), (Item::BonusItem(BonusItem::Relic), 1)]);
+        assert!(!player.inventory.contains(&other));
+        let merged = player.inventory.merge(&other);
+        assert!(merged.contains(&other) && merged.contains(&player.inventory));
+        assert_eq!(merged.get(&Item::BonusItem(BonusItem::Relic)), 3);
+
+        assert_eq!(player.inventory.remove(&Item::Teleporter(Teleporter::Shriek), 2), 1);
+        assert_eq!(player.inventory.remove(&Item::SpiritLight(1), 100), 0);
+        let mut items = player.inventory.iter().map(|(item, amount)| format!("{} {}", amount, item)).collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items, vec!["1 Arcing", "1 Shuriken", "2 Ancestral Light", "2 Relic", "50 Spirit Light"]);
+        assert!(!player.inventory.is_empty());
+        assert!(Inventory::default().is_empty());
     }
 
     #[test]
@@ -504,6 +504,28 @@ impl Requirement {
             _ => vec![],
         }
     }
+
+    pub fn replace_keystones(&mut self, amount: u16) {
+        match self {
+            Requirement::Resource(Resource::Keystone, contained) => *contained = amount,
+            Requirement::And(nested) | Requirement::Or(nested) => nested.iter_mut().for_each(|requirement| requirement.replace_keystones(amount)),
+            _ => {},
+        }
+    }
+    pub fn contained_teleporter(&self) -> Option<Teleporter> {
+        match self {
+            Requirement::Teleporter(teleporter) => Some(*teleporter),
+            Requirement::And(nested) | Requirement::Or(nested) => nested.iter().find_map(Requirement::contained_teleporter),
+            _ => None,
+        }
+    }
+    pub fn replace_teleporter(&mut self, teleporter: Teleporter, replacement: Teleporter) {
+        match self {
+            Requirement::Teleporter(contained) if *contained == teleporter => *contained = replacement,
+            Requirement::And(nested) | Requirement::Or(nested) => nested.iter_mut().for_each(|requirement| requirement.replace_teleporter(teleporter, replacement)),
+            _ => {},
+        }
+    }
 }
 
 #[cfg(test)]